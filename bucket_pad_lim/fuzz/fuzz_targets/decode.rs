@@ -0,0 +1,42 @@
+//! Decode arbitrary input : BucketPad read must return an error or a result, never panic or
+//! loop.
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate readwrite_comp;
+extern crate bucket_pad_lim;
+
+use std::io::Cursor;
+use readwrite_comp::ExtRead;
+use bucket_pad_lim::{
+  BucketPad,
+  Buckets,
+};
+
+fn decode<ER : ExtRead>(data : &[u8], er : &mut ER) {
+  let mut input = Cursor::new(data);
+  let mut buf = [0;32];
+  // at most one message per input byte
+  for _ in 0..data.len() + 1 {
+    if er.read_header(&mut input).is_err() {
+      return;
+    }
+    loop {
+      match er.read_from(&mut input, &mut buf) {
+        Ok(0) => break,
+        Ok(_) => (),
+        Err(_) => return,
+      }
+    }
+    if er.read_end(&mut input).is_err() {
+      return;
+    }
+    if input.position() as usize == data.len() {
+      return;
+    }
+  }
+}
+
+fuzz_target!(|data: &[u8]| {
+  decode(data, &mut BucketPad::seeded(Buckets::PowerOfTwo { min : 64 }, 0, 4096, 0).unwrap());
+});
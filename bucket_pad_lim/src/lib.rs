@@ -0,0 +1,444 @@
+//! Bucket padding limiter : a whole message is padded so that its frame length is one of a set of
+//! sizes (buckets), hiding message length to an observer of the (ciphered) stream.
+//!
+//! Frame is a header (content length and padding length as u64 little endian), the content and
+//! random padding. Frame length is the smallest bucket containing header, content and an optional
+//! random extra length.
+//! Message is buffered until `write_end` (frame length depends on content length), max frame size
+//! bounds this buffer and the lengths accepted on read : frame length is at most `max_frame +
+//! HEADER_LEN`, content which could not fit in such a bucket is refused (frame length is always a
+//! bucket).
+//!
+//! It is meant to be composed under a cipher (`CompExtW(BucketPad, Cipher)`), the frame being
+//! self delimited the cipher does not need to delimit message.
+
+extern crate readwrite_comp;
+extern crate readwrite_comp_test;
+extern crate byteorder;
+extern crate rand;
+
+use std::io::{
+  Write,
+  Read,
+  Result,
+  Error,
+  ErrorKind,
+  IoSlice,
+};
+use readwrite_comp::{
+  ExtRead,
+  ExtWrite,
+  write_all_from,
+  read_exact_from,
+  read_in_frame,
+  skip_in_frame,
+};
+use byteorder::{
+  ByteOrder,
+  LittleEndian,
+};
+use rand::{
+  Rng,
+  RngCore,
+  CryptoRng,
+  SeedableRng,
+};
+use rand::rngs::{
+  StdRng,
+  OsRng,
+};
+#[cfg(test)]
+use readwrite_comp_test::{
+  test_bytes_wr,
+  check_ext,
+};
+#[cfg(test)]
+use readwrite_comp_test::transport::{
+  FaultWrite,
+  ShortWrite,
+  retry_blocking,
+};
+#[cfg(test)]
+use readwrite_comp::{
+  CompExtW,
+  CompExtR,
+};
+
+/// header length (content length and padding length)
+pub const HEADER_LEN : usize = 16;
+
+/// default max frame size
+pub const DEFAULT_MAX_FRAME : usize = 16 * 1024 * 1024;
+
+/// Frame sizes
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum Buckets {
+  /// next power of two, at least `min`
+  PowerOfTwo { min : usize },
+  /// fixed increasing sizes, frames bigger than the last size are rounded to a multiple of it
+  List(Vec<usize>),
+}
+
+impl Buckets {
+  /// smallest bucket of at least `len`
+  pub fn bucket(&self, len : usize) -> usize {
+    match *self {
+      Buckets::PowerOfTwo { min } => {
+        let len = if len < min { min } else { len };
+        len.checked_next_power_of_two().unwrap_or(len)
+      },
+      Buckets::List(ref sizes) => match sizes.iter().find(|s| **s >= len) {
+        Some(s) => *s,
+        None => {
+          let last = sizes[sizes.len() - 1];
+          let r = len % last;
+          if r == 0 { len } else { len.checked_add(last - r).unwrap_or(len) }
+        },
+      },
+    }
+  }
+
+  fn check(&self) -> Result<()> {
+    if let Buckets::List(ref sizes) = *self {
+      if sizes.is_empty() || sizes[0] == 0 || sizes.windows(2).any(|w| w[0] >= w[1]) {
+        return Err(Error::new(ErrorKind::InvalidInput, "bucket sizes must be non zero and increasing"));
+      }
+    }
+    Ok(())
+  }
+}
+
+pub struct BucketPad<RNG : RngCore + CryptoRng = StdRng> {
+  buckets : Buckets,
+  /// max random length added before rounding to bucket
+  extra : usize,
+  /// max content or padding length (max frame length less header)
+  max_frame : usize,
+  rng : RNG,
+  /// header space followed by content and padding
+  wbuf : Vec<u8>,
+  /// written bytes of wbuf
+  wpos : usize,
+  /// if header and padding are in wbuf (frame is being written)
+  framed : bool,
+  /// read header bytes
+  hbuf : [u8;HEADER_LEN],
+  /// number of read header bytes
+  hlen : usize,
+  /// if header is read
+  hread : bool,
+  /// remaining content to read
+  rem : usize,
+  /// padding to skip at read end
+  pad : usize,
+}
+
+impl BucketPad<StdRng> {
+  /// padding rng is seeded from os, error if it fails or if bucket list is empty or not
+  /// increasing.
+  #[allow(clippy::io_other_error)]
+  pub fn new(buckets : Buckets, extra : usize, max_frame : usize) -> Result<Self> {
+    let rng = try!(StdRng::from_rng(OsRng).map_err(|e| Error::new(ErrorKind::Other, e)));
+    Self::with_rng(buckets, extra, max_frame, rng)
+  }
+
+  /// deterministic padding (for reproducible tests)
+  pub fn seeded(buckets : Buckets, extra : usize, max_frame : usize, seed : u64) -> Result<Self> {
+    Self::with_rng(buckets, extra, max_frame, StdRng::seed_from_u64(seed))
+  }
+}
+
+impl<RNG : RngCore + CryptoRng> BucketPad<RNG> {
+  pub fn with_rng(buckets : Buckets, extra : usize, max_frame : usize, rng : RNG) -> Result<Self> {
+    try!(buckets.check());
+    Ok(BucketPad {
+      buckets,
+      extra,
+      max_frame,
+      rng,
+      wbuf : vec![0;HEADER_LEN],
+      wpos : 0,
+      framed : false,
+      hbuf : [0;HEADER_LEN],
+      hlen : 0,
+      hread : false,
+      rem : 0,
+      pad : 0,
+    })
+  }
+
+  #[inline]
+  pub fn buckets(&self) -> &Buckets {
+    &self.buckets
+  }
+
+  /// frame length for `len` bytes (header and content) and `extra` random length, error if it
+  /// is bigger than max frame.
+  fn frame_size(&self, len : usize, extra : usize) -> Result<usize> {
+    let f = self.buckets.bucket(len.saturating_add(extra));
+    if f - HEADER_LEN > self.max_frame {
+      return Err(Error::new(ErrorKind::InvalidInput, "frame bigger than max frame size"));
+    }
+    Ok(f)
+  }
+
+  /// read header, progress is kept on transport error
+  fn read_head<R : Read>(&mut self, r : &mut R) -> Result<()> {
+    if self.hread {
+      return Ok(());
+    }
+    try!(read_exact_from(r, &mut self.hbuf, &mut self.hlen));
+    self.hlen = 0;
+    let len = LittleEndian::read_u64(&self.hbuf[..8]);
+    let pad = LittleEndian::read_u64(&self.hbuf[8..]);
+    if len > self.max_frame as u64 || pad > self.max_frame as u64 {
+      return Err(Error::new(ErrorKind::InvalidData, "frame bigger than max frame size"));
+    }
+    self.rem = len as usize;
+    self.pad = pad as usize;
+    self.hread = true;
+    Ok(())
+  }
+}
+
+impl<RNG : RngCore + CryptoRng> ExtRead for BucketPad<RNG> {
+  fn read_header<R : Read>(&mut self, r : &mut R) -> Result<()> {
+    self.read_head(r)
+  }
+
+  /// return 0 at end of content (padding is skipped at read end).
+  fn read_from<R : Read>(&mut self, r : &mut R, buf : &mut[u8]) -> Result<usize> {
+    try!(self.read_head(r));
+    read_in_frame(r, &mut self.rem, buf)
+  }
+
+  /// content is dropped without copy in caller buffer.
+  fn skip_from<R : Read>(&mut self, r : &mut R, n : usize) -> Result<usize> {
+    try!(self.read_head(r));
+    skip_in_frame(r, &mut self.rem, n)
+  }
+
+  /// skip remaining content and padding.
+  fn read_end<R : Read>(&mut self, r : &mut R) -> Result<()> {
+    try!(self.discard_to_end(r));
+    while self.pad != 0 {
+      try!(skip_in_frame(r, &mut self.pad, usize::MAX));
+    }
+    self.hread = false;
+    Ok(())
+  }
+}
+
+/// Frame is written on `write_end`.
+impl<RNG : RngCore + CryptoRng> ExtWrite for BucketPad<RNG> {
+  #[inline]
+  fn write_header<W : Write>(&mut self, _ : &mut W) -> Result<()> {
+    Ok(())
+  }
+
+  /// error if frame (with max extra length) would be bigger than max frame size (content is not
+  /// written).
+  fn write_into<W : Write>(&mut self, _ : &mut W, cont : &[u8]) -> Result<usize> {
+    try!(self.frame_size(self.wbuf.len().saturating_add(cont.len()), self.extra));
+    self.wbuf.extend_from_slice(cont);
+    Ok(cont.len())
+  }
+
+  /// all buffers are added to the frame.
+  fn write_vectored_into<W : Write>(&mut self, _ : &mut W, bufs : &[IoSlice]) -> Result<usize> {
+    let len = bufs.iter().fold(0, |l : usize, b| l.saturating_add(b.len()));
+    try!(self.frame_size(self.wbuf.len().saturating_add(len), self.extra));
+    for b in bufs.iter() {
+      self.wbuf.extend_from_slice(b);
+    }
+    Ok(len)
+  }
+
+  /// no effect : frame is written on `write_end`
+  #[inline]
+  fn flush_into<W : Write>(&mut self, _ : &mut W) -> Result<()> {
+    Ok(())
+  }
+
+  /// frame is kept on transport error, calling `write_end` again resumes its writing.
+  /// Error if an empty message frame is bigger than max frame size (smallest bucket is too big).
+  fn write_end<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    if !self.framed {
+      let len = self.wbuf.len();
+      let extra = if self.extra > 0 { self.rng.gen_range(0..self.extra + 1) } else { 0 };
+      let pad = try!(self.frame_size(len, extra)) - len;
+      LittleEndian::write_u64(&mut self.wbuf[..8], (len - HEADER_LEN) as u64);
+      LittleEndian::write_u64(&mut self.wbuf[8..HEADER_LEN], pad as u64);
+      self.wbuf.resize(len + pad, 0);
+      self.rng.fill_bytes(&mut self.wbuf[len..]);
+      self.framed = true;
+    }
+    try!(write_all_from(w, &self.wbuf, &mut self.wpos));
+    self.wbuf.truncate(HEADER_LEN);
+    self.wpos = 0;
+    self.framed = false;
+    Ok(())
+  }
+}
+
+/// xor stream cipher for tests (no message delimitation)
+#[cfg(test)]
+struct Xor(u8);
+
+#[cfg(test)]
+impl ExtWrite for Xor {
+  fn write_header<W : Write>(&mut self, _ : &mut W) -> Result<()> {
+    Ok(())
+  }
+  fn write_into<W : Write>(&mut self, w : &mut W, cont : &[u8]) -> Result<usize> {
+    let c : Vec<u8> = cont.iter().map(|b| b ^ self.0).collect();
+    w.write(&c[..])
+  }
+  fn write_end<W : Write>(&mut self, _ : &mut W) -> Result<()> {
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+impl ExtRead for Xor {
+  fn read_header<R : Read>(&mut self, _ : &mut R) -> Result<()> {
+    Ok(())
+  }
+  fn read_from<R : Read>(&mut self, r : &mut R, buf : &mut[u8]) -> Result<usize> {
+    let rr = try!(r.read(buf));
+    for b in buf[..rr].iter_mut() {
+      *b ^= self.0;
+    }
+    Ok(rr)
+  }
+  fn read_end<R : Read>(&mut self, _ : &mut R) -> Result<()> {
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+fn frame_len<EW : ExtWrite>(ew : &mut EW, len : usize) -> usize {
+  let mut w = Vec::new();
+  ew.write_header(&mut w).unwrap();
+  ew.write_all_into(&mut w, &vec![1;len][..]).unwrap();
+  ew.write_end(&mut w).unwrap();
+  w.len()
+}
+
+#[test]
+fn bucket_test () {
+  let buckets = [Buckets::PowerOfTwo { min : 64 }, Buckets::List(vec![512, 4096, 65536])];
+  for b in buckets.iter() {
+    for extra in [0, 300].iter() {
+      let new = || BucketPad::new(b.clone(), *extra, 100_000).unwrap();
+      check_ext(new, new, false).unwrap();
+      test_bytes_wr(5000, 300, &mut new(), &mut new()).unwrap();
+    }
+  }
+}
+
+#[test]
+fn bucket_size_test () {
+  let mut bp = BucketPad::seeded(Buckets::List(vec![512, 4096, 65536]), 0, DEFAULT_MAX_FRAME, 0).unwrap();
+  assert!(frame_len(&mut bp, 0) == 512);
+  assert!(frame_len(&mut bp, 512 - HEADER_LEN) == 512);
+  assert!(frame_len(&mut bp, 512 - HEADER_LEN + 1) == 4096);
+  assert!(frame_len(&mut bp, 70000) == 131072);
+  let mut bp = BucketPad::seeded(Buckets::PowerOfTwo { min : 64 }, 0, DEFAULT_MAX_FRAME, 0).unwrap();
+  assert!(frame_len(&mut bp, 1) == 64);
+  assert!(frame_len(&mut bp, 1000) == 1024);
+  // extra padding may use next bucket
+  let mut bp = BucketPad::seeded(Buckets::PowerOfTwo { min : 64 }, 1000, DEFAULT_MAX_FRAME, 0).unwrap();
+  let sizes : Vec<usize> = (0..50).map(|_| frame_len(&mut bp, 10)).collect();
+  assert!(sizes.iter().all(|s| s.is_power_of_two() && *s >= 64 && *s <= 2048));
+  assert!(sizes.iter().any(|s| *s != sizes[0]));
+  // invalid buckets
+  assert!(BucketPad::new(Buckets::List(vec![]), 0, 10).is_err());
+  assert!(BucketPad::new(Buckets::List(vec![512, 512]), 0, 10).is_err());
+}
+
+#[test]
+fn bucket_cipher_test () {
+  let new_w = || CompExtW(BucketPad::new(Buckets::List(vec![512, 4096]), 16, 100_000).unwrap(), Xor(0x5A));
+  let new_r = || CompExtR(BucketPad::new(Buckets::List(vec![512, 4096]), 16, 100_000).unwrap(), Xor(0x5A));
+  check_ext(new_w, new_r, false).unwrap();
+  // ciphered frame has bucket size
+  assert!(frame_len(&mut new_w(), 100) == 512);
+}
+
+#[test]
+fn bucket_max_test () {
+  let mut bp = BucketPad::new(Buckets::PowerOfTwo { min : 64 }, 0, 10).unwrap();
+  let mut w = Vec::new();
+  assert!(bp.write_into(&mut w, &[1;11]).unwrap_err().kind() == ErrorKind::InvalidInput);
+  let mut input = [0;HEADER_LEN];
+  input[0] = 11;
+  assert!(bp.read_header(&mut &input[..]).unwrap_err().kind() == ErrorKind::InvalidData);
+}
+
+#[test]
+fn bucket_max_frame_test () {
+  // frames near max frame are buckets or refused
+  let max = 1024 - HEADER_LEN;
+  let mut bp = BucketPad::new(Buckets::PowerOfTwo { min : 64 }, 100, max).unwrap();
+  for len in 800..max + 1 {
+    let mut w = Vec::new();
+    bp.write_header(&mut w).unwrap();
+    match bp.write_into(&mut w, &vec![1;len][..]) {
+      Ok(l) => {
+        assert!(l == len && len <= max - 100);
+        bp.write_end(&mut w).unwrap();
+        assert!(w.len() == 1024);
+      },
+      Err(e) => {
+        assert!(e.kind() == ErrorKind::InvalidInput && len > max - 100);
+        bp.write_end(&mut w).unwrap();
+        assert!(w.len().is_power_of_two() && w.len() <= 1024);
+      },
+    }
+  }
+  // bigger frames round to last bucket multiple
+  let mut bp = BucketPad::new(Buckets::List(vec![512, 4096]), 0, 4096 * 3 - HEADER_LEN).unwrap();
+  for len in [496, 497, 4096, 4096 * 3 - HEADER_LEN].iter() {
+    let l = frame_len(&mut bp, *len);
+    assert!(l == 512 || l % 4096 == 0);
+  }
+  let mut w = Vec::new();
+  assert!(bp.write_into(&mut w, &[1;4096 * 3 - HEADER_LEN + 1]).unwrap_err().kind() == ErrorKind::InvalidInput);
+  // empty message frame is too big
+  let mut bp = BucketPad::new(Buckets::PowerOfTwo { min : 64 }, 0, 10).unwrap();
+  bp.write_header(&mut w).unwrap();
+  assert!(bp.write_end(&mut w).unwrap_err().kind() == ErrorKind::InvalidInput);
+  assert!(w.is_empty());
+}
+
+#[test]
+fn bucket_write_end_retry_test () {
+  let mut bp = BucketPad::new(Buckets::PowerOfTwo { min : 64 }, 0, 1000).unwrap();
+  let mut w = FaultWrite::new(ShortWrite(Vec::new(), 16), ErrorKind::WouldBlock, 2);
+  bp.write_header(&mut w).unwrap();
+  bp.write_all_into(&mut w, &[1,2,3]).unwrap();
+  assert!(bp.write_end(&mut w).unwrap_err().kind() == ErrorKind::WouldBlock);
+  retry_blocking(&[ErrorKind::WouldBlock], || bp.write_end(&mut w)).unwrap();
+  assert!((w.inner).0.len() == 64);
+  // next frame starts from an empty buffer
+  bp.write_header(&mut w).unwrap();
+  bp.write_all_into(&mut w, &[4]).unwrap();
+  retry_blocking(&[ErrorKind::WouldBlock], || bp.write_end(&mut w)).unwrap();
+  assert!((w.inner).0.len() == 128);
+  let mut r = &(w.inner).0[..];
+  let mut buf = Vec::new();
+  for expected in [&[1,2,3][..], &[4][..]].iter() {
+    buf.clear();
+    bp.read_header(&mut r).unwrap();
+    let mut b = [0;10];
+    loop {
+      let n = bp.read_from(&mut r, &mut b).unwrap();
+      if n == 0 { break }
+      buf.extend_from_slice(&b[..n]);
+    }
+    bp.read_end(&mut r).unwrap();
+    assert!(&buf[..] == *expected);
+  }
+  assert!(r.is_empty());
+}
@@ -0,0 +1,336 @@
+//! COBS (Consistent Overhead Byte Stuffing) limiter : content is written as groups of non zero
+//! bytes, each group starting with a code byte (group length + 1), and message ends with a zero
+//! byte.
+//! A group with code 0xFF contains 254 bytes and is not followed by a content zero, other groups
+//! are. Overhead is at most one byte per 254 bytes (plus the end zero).
+//!
+//! Write is streaming but a group can only be written when its length is known : up to 254
+//! bytes are kept until a zero is written, the group is full or message end is written (flush
+//! does not write them).
+//! Read does not read after the end zero (code bytes are read one by one).
+
+extern crate readwrite_comp;
+extern crate readwrite_comp_test;
+extern crate memchr;
+
+use std::io::{
+  Write,
+  Read,
+  Result,
+  Error,
+  ErrorKind,
+};
+use readwrite_comp::{
+  ExtRead,
+  ExtWrite,
+};
+use memchr::memchr;
+#[cfg(test)]
+use readwrite_comp_test::{
+  test_bytes_wr,
+  check_ext,
+};
+#[cfg(test)]
+use readwrite_comp_test::transport::FaultWrite;
+
+/// max number of bytes in a group
+const MAX_GROUP : usize = 254;
+
+pub struct Cobs {
+  /// content of current group (not written)
+  group : Vec<u8>,
+  /// last written group is a full group (no content zero after it)
+  full : bool,
+  /// if end zero is read (waiting for read end)
+  rend : bool,
+  /// if end zero is pending for write (write end not finished)
+  wend : bool,
+  /// encoded content not yet written
+  wpending : Vec<u8>,
+  /// written bytes of wpending
+  wpos : usize,
+  /// bytes remaining in read group
+  rem : usize,
+  /// a content zero is read if next code is not end
+  zero : bool,
+}
+
+impl Default for Cobs {
+  fn default() -> Self {
+    Cobs::new()
+  }
+}
+
+impl Cobs {
+  pub fn new() -> Self {
+    Cobs {
+      group : Vec::with_capacity(MAX_GROUP),
+      full : false,
+      rend : false,
+      wend : false,
+      wpending : Vec::new(),
+      wpos : 0,
+      rem : 0,
+      zero : false,
+    }
+  }
+
+  /// encode current group
+  fn push_group(&mut self) {
+    self.wpending.push(self.group.len() as u8 + 1);
+    self.wpending.extend_from_slice(&self.group[..]);
+    self.full = self.group.len() == MAX_GROUP;
+    self.group.clear();
+  }
+
+  /// write pending encoded content
+  fn drain<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    while self.wpos < self.wpending.len() {
+      match w.write(&self.wpending[self.wpos..]) {
+        Ok(0) => return Err(Error::new(ErrorKind::WriteZero, "failed to write encoded content")),
+        Ok(n) => self.wpos += n,
+        Err(ref e) if e.kind() == ErrorKind::Interrupted => (),
+        Err(e) => return Err(e),
+      }
+    }
+    self.wpending.clear();
+    self.wpos = 0;
+    Ok(())
+  }
+
+  /// read a byte, None at end of stream
+  fn read_byte<R : Read>(r : &mut R) -> Result<Option<u8>> {
+    let mut b = [0];
+    loop {
+      match r.read(&mut b) {
+        Ok(0) => return Ok(None),
+        Ok(_) => return Ok(Some(b[0])),
+        Err(ref e) if e.kind() == ErrorKind::Interrupted => (),
+        Err(e) => return Err(e),
+      }
+    }
+  }
+}
+
+impl ExtRead for Cobs {
+  #[inline]
+  fn read_header<R : Read>(&mut self, _ : &mut R) -> Result<()> {
+    Ok(())
+  }
+
+  /// return 0 if ended, a call returns content of at most one group (and the content zero before
+  /// it).
+  /// End of stream before end zero is an `UnexpectedEof` error, zero in a group an `InvalidData`
+  /// error.
+  fn read_from<R : Read>(&mut self, r : &mut R, buf : &mut[u8]) -> Result<usize> {
+    if self.rend || buf.is_empty() {
+      return Ok(0);
+    }
+    let mut i = 0;
+    while self.rem == 0 {
+      match try!(Cobs::read_byte(r)) {
+        None => return Err(Error::new(ErrorKind::UnexpectedEof, "end of stream before end zero")),
+        Some(0) => {
+          self.rend = true;
+          self.zero = false;
+          return Ok(0);
+        },
+        Some(c) => {
+          if self.zero {
+            buf[0] = 0;
+            i = 1;
+          }
+          self.zero = c as usize != MAX_GROUP + 1;
+          self.rem = c as usize - 1;
+          if i == buf.len() || (i > 0 && self.rem == 0) {
+            return Ok(i);
+          }
+        },
+      }
+    }
+    let l = if self.rem < buf.len() - i { self.rem } else { buf.len() - i };
+    let rr = loop {
+      match r.read(&mut buf[i..i + l]) {
+        Ok(0) => return if i > 0 {
+          Ok(i)
+        } else {
+          Err(Error::new(ErrorKind::UnexpectedEof, "end of stream in group"))
+        },
+        Ok(rr) => break rr,
+        Err(ref e) if e.kind() == ErrorKind::Interrupted => (),
+        Err(e) => return if i > 0 { Ok(i) } else { Err(e) },
+      }
+    };
+    if memchr(0, &buf[i..i + rr]).is_some() {
+      return Err(Error::new(ErrorKind::InvalidData, "zero in group"));
+    }
+    self.rem -= rr;
+    Ok(i + rr)
+  }
+
+  /// if end zero is not read, remaining content is skipped.
+  fn read_end<R : Read>(&mut self, r : &mut R) -> Result<()> {
+    try!(self.discard_to_end(r));
+    self.rend = false;
+    Ok(())
+  }
+}
+
+/// encoded groups are written with as few write as possible. An error before any byte of a new
+/// group is written is returned (content is not accepted), once some bytes are written (or if
+/// content stays in current group) the content is reported as written and an error on the write of
+/// the remaining bytes will be returned on next call (or on flush or write end).
+impl ExtWrite for Cobs {
+  #[inline]
+  fn write_header<W : Write>(&mut self, _ : &mut W) -> Result<()> {
+    Ok(())
+  }
+
+  fn write_into<W : Write>(&mut self, w : &mut W, mut cont : &[u8]) -> Result<usize> {
+    try!(self.drain(w));
+    let len = cont.len();
+    // restored if content is not accepted
+    let (group, full) = (self.group.clone(), self.full);
+    while !cont.is_empty() {
+      let room = MAX_GROUP - self.group.len();
+      let l = if cont.len() < room { cont.len() } else { room };
+      match memchr(0, &cont[..l]) {
+        Some(ix) => {
+          self.group.extend_from_slice(&cont[..ix]);
+          cont = &cont[ix + 1..];
+          self.push_group();
+        },
+        None => {
+          self.group.extend_from_slice(&cont[..l]);
+          cont = &cont[l..];
+          if self.group.len() == MAX_GROUP {
+            self.push_group();
+          }
+        },
+      }
+    }
+    match self.drain(w) {
+      Err(e) if self.wpos == 0 => {
+        self.wpending.clear();
+        self.group = group;
+        self.full = full;
+        Err(e)
+      },
+      // content is accepted, remaining pending is for next call
+      _ => Ok(len),
+    }
+  }
+
+  /// only complete groups are written
+  #[inline]
+  fn flush_into<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    self.drain(w)
+  }
+
+  fn write_end<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    if !self.wend {
+      try!(self.drain(w));
+      // no empty group after a full group
+      if !(self.full && self.group.is_empty()) {
+        self.push_group();
+      }
+      self.wpending.push(0);
+      self.full = false;
+      self.wend = true;
+    }
+    try!(self.drain(w));
+    self.wend = false;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+fn encode(cont : &[u8]) -> Vec<u8> {
+  let mut c = Cobs::new();
+  let mut w = Vec::new();
+  c.write_all_into(&mut w, cont).unwrap();
+  c.write_end(&mut w).unwrap();
+  w
+}
+
+#[test]
+fn cobs_test () {
+  let mut c = Cobs::new();
+  let mut c2 = Cobs::new();
+  for &(l, b) in [(150, 7), (1000, 300), (600, 1), (0, 3)].iter() {
+    test_bytes_wr(
+      l,
+      b,
+      &mut c,
+      &mut c2,
+    ).unwrap();
+  }
+  check_ext(Cobs::new, Cobs::new, false).unwrap();
+}
+
+#[test]
+fn cobs_encoding_test () {
+  assert!(encode(&[]) == vec![1, 0]);
+  assert!(encode(&[0]) == vec![1, 1, 0]);
+  assert!(encode(&[0, 0]) == vec![1, 1, 1, 0]);
+  assert!(encode(&[0x11, 0x22, 0, 0x33]) == vec![3, 0x11, 0x22, 2, 0x33, 0]);
+  assert!(encode(&[0x11, 0x22, 0x33, 0x44]) == vec![5, 0x11, 0x22, 0x33, 0x44, 0]);
+  let cont : Vec<u8> = (1..256).map(|i| i as u8).collect();
+  let mut exp = vec![0xFF];
+  exp.extend_from_slice(&cont[..254]);
+  exp.extend_from_slice(&[2, 0xFF, 0]);
+  assert!(encode(&cont[..]) == exp);
+  exp.truncate(255);
+  exp.push(0);
+  assert!(encode(&cont[..254]) == exp);
+  // overhead
+  let cont = vec![1;10_000];
+  assert!(encode(&cont[..]).len() <= cont.len() + cont.len() / 254 + 2);
+}
+
+#[test]
+fn cobs_both_directions_test () {
+  // same instance reading and writing
+  let mut c = Cobs::new();
+  let mut input = &[2, 5, 0][..];
+  let mut buf = [0;8];
+  assert!(c.read_from(&mut input, &mut buf).unwrap() == 1);
+  assert!(c.read_from(&mut input, &mut buf).unwrap() == 0);
+  // end is read (read end not called) while writing a message
+  let mut w = Vec::new();
+  c.write_all_into(&mut w, &[3]).unwrap();
+  c.write_end(&mut w).unwrap();
+  assert!(w == encode(&[3]));
+  assert!(c.read_from(&mut input, &mut buf).unwrap() == 0);
+  c.read_end(&mut input).unwrap();
+}
+
+#[test]
+fn cobs_invalid_test () {
+  let mut c = Cobs::new();
+  let mut buf = [0;8];
+  let mut input = &[3, 1, 0, 0][..];
+  assert!(c.read_from(&mut input, &mut buf).unwrap_err().kind() == ErrorKind::InvalidData);
+  let mut c = Cobs::new();
+  let mut input = &[3, 1][..];
+  assert!(c.read_from(&mut input, &mut buf).unwrap() == 1);
+  assert!(c.read_from(&mut input, &mut buf).unwrap_err().kind() == ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn cobs_write_error_test () {
+  let mut c = Cobs::new();
+  // every second write fails
+  let mut w = FaultWrite::new(Vec::new(), ErrorKind::Other, 2);
+  assert!(c.write_into(&mut w, &[1,0,2]).unwrap() == 3);
+  // content is not accepted if none of its groups is written
+  assert!(c.write_into(&mut w, &[3,0]).is_err());
+  assert!(c.write_into(&mut w, &[3,0]).unwrap() == 2);
+  assert!(c.write_end(&mut w).is_err());
+  c.write_end(&mut w).unwrap();
+  let mut expected = Vec::new();
+  let mut c = Cobs::new();
+  c.write_all_into(&mut expected, &[1,0,2,3,0]).unwrap();
+  c.write_end(&mut expected).unwrap();
+  assert!(w.inner == expected);
+}
@@ -0,0 +1,212 @@
+//! Buffered EscapeTerm : same wire format as `EscapeTerm` (with the same `EscapeConfig`), but
+//! reading is done by blocks in an internal buffer, and content is copied up to the next escape
+//! byte (searched with `memchr`) instead of byte per byte.
+//!
+//! Bytes read after the end sequence are kept in the buffer for the next message. When switching
+//! to another reader (raw transport or other limiter), they must be given back through
+//! `take_lookahead` or `give_back`.
+
+use std::io::{
+  Write,
+  Read,
+  Result,
+  Error,
+  ErrorKind,
+  Cursor,
+  Chain,
+};
+use readwrite_comp::{
+  ExtRead,
+  ExtWrite,
+};
+use memchr::{
+  memchr,
+  memchr2,
+};
+use super::{
+  EscapeTerm,
+  EscapeConfig,
+  Step,
+};
+
+/// default size of read buffer
+pub const DEFAULT_CAPACITY : usize = 4096;
+
+pub struct BufEscapeTerm {
+  /// configuration, write side and read state
+  et : EscapeTerm,
+  /// read buffer
+  rbuf : Vec<u8>,
+  /// start of unread bytes in rbuf
+  rpos : usize,
+  /// end of unread bytes in rbuf
+  rlen : usize,
+}
+
+impl BufEscapeTerm {
+  #[inline]
+  pub fn new(t : u8) -> Self {
+    Self::with_capacity(t, DEFAULT_CAPACITY)
+  }
+
+  /// `cap` is the size of read buffer (at least 1).
+  pub fn with_capacity(t : u8, cap : usize) -> Self {
+    Self::from_escape_term(EscapeTerm::new(t), cap)
+  }
+
+  /// error if configuration is invalid (see `EscapeConfig::check`).
+  pub fn with_config(conf : EscapeConfig, cap : usize) -> Result<Self> {
+    Ok(Self::from_escape_term(try!(EscapeTerm::with_config(conf)), cap))
+  }
+
+  fn from_escape_term(et : EscapeTerm, cap : usize) -> Self {
+    BufEscapeTerm {
+      et,
+      rbuf : vec![0;if cap == 0 { 1 } else { cap }],
+      rpos : 0,
+      rlen : 0,
+    }
+  }
+
+  #[inline]
+  pub fn config(&self) -> &EscapeConfig {
+    self.et.config()
+  }
+
+  /// see `EscapeTerm::write_keepalive`
+  #[inline]
+  pub fn write_keepalive<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    self.et.write_keepalive(w)
+  }
+
+  /// see `EscapeTerm::write_abort`
+  #[inline]
+  pub fn write_abort<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    self.et.write_abort(w)
+  }
+
+  /// bytes read from transport but not consumed (after end sequence : next message content or
+  /// content for another reader).
+  #[inline]
+  pub fn lookahead(&self) -> &[u8] {
+    &self.rbuf[self.rpos..self.rlen]
+  }
+
+  /// remove and return unconsumed bytes.
+  pub fn take_lookahead(&mut self) -> Vec<u8> {
+    let res = self.lookahead().to_vec();
+    self.rpos = 0;
+    self.rlen = 0;
+    res
+  }
+
+  /// reader over unconsumed bytes followed by `r`, to use in place of `r` after the end of
+  /// escaped content.
+  #[inline]
+  pub fn give_back<R : Read>(&mut self, r : R) -> Chain<Cursor<Vec<u8>>, R> {
+    Cursor::new(self.take_lookahead()).chain(r)
+  }
+
+  /// fill empty read buffer, return false at end of stream
+  fn fill<R : Read>(&mut self, r : &mut R) -> Result<bool> {
+    loop {
+      match r.read(&mut self.rbuf[..]) {
+        Ok(n) => {
+          self.rpos = 0;
+          self.rlen = n;
+          return Ok(n > 0);
+        },
+        Err(ref e) if e.kind() == ErrorKind::Interrupted => (),
+        Err(e) => return Err(e),
+      }
+    }
+  }
+}
+
+impl ExtRead for BufEscapeTerm {
+  #[inline]
+  fn read_header<R : Read>(&mut self, _ : &mut R) -> Result<()> {
+    Ok(())
+  }
+
+  /// return 0 if ended, transport is only read when there is no buffered bytes (and no content
+  /// read in this call).
+  fn read_from<R : Read>(&mut self, r : &mut R, buf : &mut[u8]) -> Result<usize> {
+    if self.et.rend {
+      return self.et.ended();
+    }
+    let esc = self.et.conf.esc;
+    let raw_end = self.et.conf.raw_end();
+    let mut i = 0;
+    while i < buf.len() {
+      if self.rpos == self.rlen {
+        if i > 0 {
+          return Ok(i);
+        }
+        if !try!(self.fill(r)) {
+          return Err(if self.et.escaped {
+            Error::new(ErrorKind::UnexpectedEof, "truncated escape sequence")
+          } else {
+            Error::new(ErrorKind::UnexpectedEof, "end of stream before end sequence")
+          });
+        }
+      }
+      if !self.et.escaped {
+        // copy content up to next special byte
+        let l = ::std::cmp::min(self.rlen - self.rpos, buf.len() - i);
+        let scan = &self.rbuf[self.rpos..self.rpos + l];
+        let l = match raw_end {
+          Some(end) => memchr2(esc, end, scan),
+          None => memchr(esc, scan),
+        }.unwrap_or(l);
+        buf[i..i + l].copy_from_slice(&scan[..l]);
+        i += l;
+        self.rpos += l;
+        if self.rpos == self.rlen || i == buf.len() {
+          continue;
+        }
+      }
+      let b = self.rbuf[self.rpos];
+      self.rpos += 1;
+      match try!(self.et.decode(b)) {
+        Step::Content(c) => {
+          buf[i] = c;
+          i += 1;
+        },
+        Step::Skip => (),
+        Step::End => return Ok(i),
+      }
+    }
+    Ok(i)
+  }
+
+  /// if end sequence is not read, remaining content is skipped.
+  fn read_end<R : Read>(&mut self, r : &mut R) -> Result<()> {
+    if !self.et.rend {
+      try!(self.discard_to_end(r));
+    }
+    self.et.rend = false;
+    self.et.aborted = false;
+    Ok(())
+  }
+}
+
+/// same as `EscapeTerm` write
+impl ExtWrite for BufEscapeTerm {
+  #[inline]
+  fn write_header<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    self.et.write_header(w)
+  }
+  #[inline]
+  fn write_into<W : Write>(&mut self, w : &mut W, cont : &[u8]) -> Result<usize> {
+    self.et.write_into(w, cont)
+  }
+  #[inline]
+  fn flush_into<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    self.et.flush_into(w)
+  }
+  #[inline]
+  fn write_end<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    self.et.write_end(w)
+  }
+}
@@ -3,31 +3,386 @@
 //! This sequence is escaped in stream through escape bytes
 //! Mostly for test purpose (read byte per byte). Less usefull now that bytes_wr does not have its
 //! own traits anymore but is simply ExtWrite and ExtRead for Composable use
+//! Escape byte, terminator and control codes are configurable (`EscapeConfig`, with SLIP and
+//! zero delimited presets).
+//! `BufEscapeTerm` is the same codec with block reads (see `buffered` module).
 
 extern crate readwrite_comp;
 extern crate readwrite_comp_test;
+extern crate memchr;
+#[cfg(feature="async")]
+extern crate futures_io;
   // TODO if esc char check for esc seq
   // if esc char in content wr esc two times
 use std::io::{
   Write,
   Read,
   Result,
+  Error,
+  ErrorKind,
 };
 use readwrite_comp::{
   ExtRead,
   ExtWrite,
 };
+#[cfg(feature="async")]
+use readwrite_comp::{
+  AsyncExtRead,
+  AsyncExtWrite,
+  PollBuf,
+};
+#[cfg(feature="async")]
+use futures_io::{
+  AsyncRead,
+  AsyncWrite,
+};
+#[cfg(feature="async")]
+use std::pin::Pin;
+#[cfg(feature="async")]
+use std::task::{
+  Context,
+  Poll,
+};
+use memchr::{
+  memchr,
+  memchr2,
+};
+
+/// return on pending or error, similar to `try!` for `Poll<Result<T>>`
+#[cfg(feature="async")]
+macro_rules! try_ready {($e:expr) => (
+  match $e {
+    Poll::Ready(Ok(t)) => t,
+    Poll::Ready(Err(e)) => return Poll::Ready(Err(From::from(e))),
+    Poll::Pending => return Poll::Pending,
+  }
+)}
+
+pub mod buffered;
+pub use buffered::BufEscapeTerm;
+
 #[cfg(test)]
 use readwrite_comp_test::{
   test_bytes_wr,
+  check_symmetry,
+  check_transports,
+  check_ext,
+  SymmetryConfig,
+};
+#[cfg(test)]
+use readwrite_comp_test::transport::FaultWrite;
+#[cfg(all(test,feature="async"))]
+use std::io::Cursor;
+#[cfg(all(test,feature="async"))]
+use readwrite_comp_test::async_test::{
+  block_poll,
+  PendingIo,
+  test_bytes_wr_async,
+  test_bytes_w_sync_r_async,
+  test_bytes_w_async_r_sync,
 };
 
-/// contain esc byte, and if escaped, and if waiting for end read
-pub struct EscapeTerm (u8,bool,bool);
+/// End of message
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum Terminator {
+  /// `esc` followed by this code, content is not escaped for it.
+  Escaped(u8),
+  /// raw `end` byte, occurences of `end` in content are written as `esc` followed by `code`.
+  Raw { end : u8, code : u8 },
+}
+
+/// Escape configuration : content `esc` byte is written as `esc` followed by `esc_code`, other
+/// codes following `esc` are the terminator (if escaped) and optional out of band controls.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub struct EscapeConfig {
+  pub esc : u8,
+  pub esc_code : u8,
+  pub term : Terminator,
+  /// abort current message (read returns a `ConnectionAborted` error)
+  pub abort : Option<u8>,
+  /// keepalive, ignored by reader
+  pub keepalive : Option<u8>,
+}
+
+/// Decoded byte following `esc`
+enum Escaped {
+  Content(u8),
+  End,
+  Abort,
+  KeepAlive,
+}
+
+impl EscapeConfig {
+  /// initial format : `esc` is doubled in content and end is `esc` followed by 0 (1 if `esc` is
+  /// 0).
+  pub fn new(esc : u8) -> Self {
+    EscapeConfig {
+      esc,
+      esc_code : esc,
+      term : Terminator::Escaped(if esc == 0 { 1 } else { 0 }),
+      abort : None,
+      keepalive : None,
+    }
+  }
+
+  /// SLIP (RFC 1055) framing : END 0xC0, ESC 0xDB, ESC_END 0xDC, ESC_ESC 0xDD.
+  pub fn slip() -> Self {
+    EscapeConfig {
+      esc : 0xDB,
+      esc_code : 0xDD,
+      term : Terminator::Raw { end : 0xC0, code : 0xDC },
+      abort : None,
+      keepalive : None,
+    }
+  }
+
+  /// zero terminated frames without zero in content, zero and escape byte are escaped HDLC style
+  /// (escape 0x7D, codes are escaped byte xor 0x20) : this is byte stuffing, not COBS, escaped
+  /// content can be twice content length (see `cobs_lim` crate for COBS, at most one byte per 254).
+  pub fn zero_delimited() -> Self {
+    EscapeConfig {
+      esc : 0x7D,
+      esc_code : 0x5D,
+      term : Terminator::Raw { end : 0x00, code : 0x20 },
+      abort : None,
+      keepalive : None,
+    }
+  }
+
+  pub fn with_abort(mut self, code : u8) -> Self {
+    self.abort = Some(code);
+    self
+  }
+
+  pub fn with_keepalive(mut self, code : u8) -> Self {
+    self.keepalive = Some(code);
+    self
+  }
+
+  /// check that codes following `esc` are distinct and that raw end is not `esc`.
+  pub fn check(&self) -> Result<()> {
+    let mut codes = vec![self.esc_code];
+    match self.term {
+      Terminator::Escaped(c) => codes.push(c),
+      Terminator::Raw { end, code } => {
+        if end == self.esc {
+          return Err(Error::new(ErrorKind::InvalidInput, "raw end byte must differ from escape byte"));
+        }
+        codes.push(code);
+      },
+    }
+    codes.extend(self.abort.iter().chain(self.keepalive.iter()));
+    for i in 1..codes.len() {
+      if codes[..i].contains(&codes[i]) {
+        return Err(Error::new(ErrorKind::InvalidInput, "escape codes must be distinct"));
+      }
+    }
+    Ok(())
+  }
+
+  #[inline]
+  fn raw_end(&self) -> Option<u8> {
+    match self.term {
+      Terminator::Raw { end, .. } => Some(end),
+      Terminator::Escaped(_) => None,
+    }
+  }
+
+  /// end sequence and its length
+  fn end_seq(&self) -> ([u8;2], usize) {
+    match self.term {
+      Terminator::Escaped(c) => ([self.esc, c], 2),
+      Terminator::Raw { end, .. } => ([end, 0], 1),
+    }
+  }
+
+  fn unescape(&self, b : u8) -> Result<Escaped> {
+    if b == self.esc_code {
+      return Ok(Escaped::Content(self.esc));
+    }
+    match self.term {
+      Terminator::Escaped(c) if c == b => return Ok(Escaped::End),
+      Terminator::Raw { end, code } if code == b => return Ok(Escaped::Content(end)),
+      _ => (),
+    }
+    if self.abort == Some(b) {
+      Ok(Escaped::Abort)
+    } else if self.keepalive == Some(b) {
+      Ok(Escaped::KeepAlive)
+    } else {
+      Err(Error::new(ErrorKind::InvalidData, "invalid escape sequence"))
+    }
+  }
+
+  /// append escaped content to `dest`
+  fn escape_into(&self, mut cont : &[u8], dest : &mut Vec<u8>) {
+    loop {
+      let ix = match self.raw_end() {
+        Some(end) => memchr2(self.esc, end, cont),
+        None => memchr(self.esc, cont),
+      };
+      match ix {
+        Some(i) => {
+          dest.extend_from_slice(&cont[..i]);
+          dest.push(self.esc);
+          dest.push(if cont[i] == self.esc {
+            self.esc_code
+          } else {
+            match self.term {
+              Terminator::Raw { code, .. } => code,
+              Terminator::Escaped(_) => unreachable!(),
+            }
+          });
+          cont = &cont[i + 1..];
+        },
+        None => {
+          dest.extend_from_slice(cont);
+          return;
+        },
+      }
+    }
+  }
+
+  /// control sequence for an optional code
+  fn control(&self, code : Option<u8>) -> Result<[u8;2]> {
+    match code {
+      Some(c) => Ok([self.esc, c]),
+      None => Err(Error::new(ErrorKind::InvalidInput, "control code not configured")),
+    }
+  }
+}
+
+/// Decoded byte
+enum Step {
+  Content(u8),
+  /// escape or control
+  Skip,
+  End,
+}
+
+#[inline]
+fn aborted() -> Error {
+  Error::new(ErrorKind::ConnectionAborted, "message aborted")
+}
+
+pub struct EscapeTerm {
+  conf : EscapeConfig,
+  /// if escaped
+  escaped : bool,
+  /// if end sequence is read (waiting for read end)
+  rend : bool,
+  /// if end sequence is pending for write (write end not finished)
+  wend : bool,
+  /// if message read was aborted
+  aborted : bool,
+  /// escaped content not yet written
+  wpending : Vec<u8>,
+  /// written bytes of wpending
+  wpos : usize,
+  /// async only : escaped bytes pending for write
+  #[cfg(feature="async")]
+  apending : PollBuf,
+}
 
 impl EscapeTerm {
   pub fn new (t : u8) -> Self {
-    EscapeTerm (t,false,false)
+    Self::new_unchecked(EscapeConfig::new(t))
+  }
+
+  /// error if configuration is invalid (see `EscapeConfig::check`).
+  pub fn with_config(conf : EscapeConfig) -> Result<Self> {
+    try!(conf.check());
+    Ok(Self::new_unchecked(conf))
+  }
+
+  fn new_unchecked(conf : EscapeConfig) -> Self {
+    EscapeTerm {
+      conf,
+      escaped : false,
+      rend : false,
+      wend : false,
+      aborted : false,
+      wpending : Vec::new(),
+      wpos : 0,
+      #[cfg(feature="async")]
+      apending : PollBuf::new(),
+    }
+  }
+
+  #[inline]
+  pub fn config(&self) -> &EscapeConfig {
+    &self.conf
+  }
+
+  /// write keepalive control (error if not configured), it can be written in or between messages.
+  pub fn write_keepalive<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    let c = try!(self.conf.control(self.conf.keepalive));
+    try!(self.drain(w));
+    self.wpending.extend_from_slice(&c[..]);
+    self.drain(w)
+  }
+
+  /// write abort control (error if not configured), current message is ended (no `write_end`
+  /// needed).
+  pub fn write_abort<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    let c = try!(self.conf.control(self.conf.abort));
+    try!(self.drain(w));
+    self.wpending.extend_from_slice(&c[..]);
+    self.wend = false;
+    self.drain(w)
+  }
+
+  /// write pending escaped content
+  fn drain<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    while self.wpos < self.wpending.len() {
+      match w.write(&self.wpending[self.wpos..]) {
+        Ok(0) => return Err(Error::new(ErrorKind::WriteZero, "failed to write escaped content")),
+        Ok(n) => self.wpos += n,
+        Err(ref e) if e.kind() == ErrorKind::Interrupted => (),
+        Err(e) => return Err(e),
+      }
+    }
+    self.wpending.clear();
+    self.wpos = 0;
+    Ok(())
+  }
+
+  /// decode a read byte
+  fn decode(&mut self, b : u8) -> Result<Step> {
+    if self.escaped {
+      self.escaped = false;
+      match try!(self.conf.unescape(b)) {
+        Escaped::Content(c) => Ok(Step::Content(c)),
+        Escaped::KeepAlive => Ok(Step::Skip),
+        Escaped::End => {
+          self.rend = true;
+          Ok(Step::End)
+        },
+        Escaped::Abort => {
+          self.rend = true;
+          self.aborted = true;
+          Err(aborted())
+        },
+      }
+    } else if b == self.conf.esc {
+      self.escaped = true;
+      Ok(Step::Skip)
+    } else if Some(b) == self.conf.raw_end() {
+      self.rend = true;
+      Ok(Step::End)
+    } else {
+      Ok(Step::Content(b))
+    }
+  }
+
+  /// result of read when ended
+  #[inline]
+  fn ended(&self) -> Result<usize> {
+    if self.aborted {
+      Err(aborted())
+    } else {
+      Ok(0)
+    }
   }
 }
 
@@ -36,54 +391,63 @@ impl ExtRead for EscapeTerm {
   fn read_header<R : Read>(&mut self, _ : &mut R) -> Result<()> {
     Ok(())
   }
-  /// return 0 if ended (content might still be read afterward on reader but endof BytesWR
+  /// return 0 if ended (content might still be read afterward on reader but endof BytesWR.
+  /// End of stream before end sequence is an `UnexpectedEof` error, an aborted message is a
+  /// `ConnectionAborted` error until `read_end` and an invalid escape code an `InvalidData` error
+  /// (content read in the same call is lost on error).
   fn read_from<R : Read>(&mut self, r : &mut R, buf : &mut[u8]) -> Result<usize> {
     let mut b = [0];
-    if self.2 {
-      return Ok(0);
+    if self.rend {
+      return self.ended();
     }
     let mut i = 0;
     while i < buf.len() {
-      let rr = try!(r.read(&mut b[..]));
+      let rr = match r.read(&mut b[..]) {
+        Ok(rr) => rr,
+        Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+        Err(e) => return if i > 0 { Ok(i) } else { Err(e) },
+      };
       if rr == 0 {
-        return Ok(i);
-      }
-      if b[0] == self.0 {
-        if self.1 {
-        //debug!("An escaped char read effectively");
-          buf[i] = b[0];
-          i += 1;
-          self.1 = false;
+        return if i > 0 {
+          // error on next read
+          Ok(i)
+        } else if self.escaped {
+          Err(Error::new(ErrorKind::UnexpectedEof, "truncated escape sequence"))
         } else {
-        //debug!("An escaped char read start");
-          self.1 = true;
-        }
-      } else {
-        if self.1 {
-        //debug!("An escaped end");
-          // end
-          self.2 = true;
-          return Ok(i);
-        } else {
-          buf[i] = b[0]; 
+          Err(Error::new(ErrorKind::UnexpectedEof, "end of stream before end sequence"))
+        };
+      }
+      match try!(self.decode(b[0])) {
+        Step::Content(c) => {
+          buf[i] = c;
           i += 1;
-        }
+        },
+        Step::Skip => (),
+        Step::End => return Ok(i),
       }
-
     }
     Ok(i)
   }
 
-  /// end read : we know the read is complete (for instance rust serialize object decoded), some
+  /// end read : we know the read is complete (for instance rust serialize object decoded), some
   /// finalize operation may be added (for instance read/drop padding bytes).
+  /// If end sequence is not read, remaining content is skipped.
   #[inline]
-  fn read_end<R : Read>(&mut self, _ : &mut R) -> Result<()> {
-    self.2 = false;
+  fn read_end<R : Read>(&mut self, r : &mut R) -> Result<()> {
+    if !self.rend {
+      try!(self.discard_to_end(r));
+    }
+    self.rend = false;
+    self.aborted = false;
     Ok(())
   }
 
 }
 
+/// escaped content is buffered and written with as few write as possible. An error before any
+/// byte of the content is written is returned (content is not accepted), once some bytes are
+/// written the content is reported as written and an error on the write of the remaining bytes
+/// will be returned on next call (or on flush or write end).
 impl ExtWrite for EscapeTerm {
   #[inline]
   fn write_header<W : Write>(&mut self, _ : &mut W) -> Result<()> {
@@ -91,34 +455,137 @@ impl ExtWrite for EscapeTerm {
   }
 
   fn write_into<W : Write>(&mut self, w : &mut W, cont : &[u8]) -> Result<usize> {
-    for i in 0.. cont.len() {
-      let b = cont[i];
-      if b == self.0 {
-        //debug!("An escaped char");
-        try!(w.write(&cont[i..i+1]));
-        try!(w.write(&cont[i..i+1]));
-      } else {
-        try!(w.write(&cont[i..i+1]));
-      }
+    try!(self.drain(w));
+    self.conf.escape_into(cont, &mut self.wpending);
+    match self.drain(w) {
+      Err(e) if self.wpos == 0 => {
+        self.wpending.clear();
+        Err(e)
+      },
+      // content is accepted, remaining pending is for next call
+      _ => Ok(cont.len()),
     }
-    Ok(cont.len())
+  }
+
+  #[inline]
+  fn flush_into<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    self.drain(w)
   }
 
   /// end of content write
   #[inline]
   fn write_end<W : Write>(&mut self, w : &mut W) -> Result<()> {
-    let two = if self.0 == 0 {
-      try!(w.write(&[self.0,1]))
-    }else {
-      try!(w.write(&[self.0,0]))
-    };
-    // TODO clean io error if two is not 2
-    assert!(two == 2);
+    if !self.wend {
+      try!(self.drain(w));
+      let (end, l) = self.conf.end_seq();
+      self.wpending.extend_from_slice(&end[..l]);
+      self.wend = true;
+    }
+    try!(self.drain(w));
+    self.wend = false;
     Ok(())
   }
 }
 
 
+#[cfg(feature="async")]
+impl AsyncExtRead for EscapeTerm {
+  #[inline]
+  fn poll_read_header<R : AsyncRead + Unpin>(&mut self, _ : &mut Context, _ : &mut R) -> Poll<Result<()>> {
+    Poll::Ready(Ok(()))
+  }
+
+  fn poll_read_from<R : AsyncRead + Unpin>(&mut self, cx : &mut Context, r : &mut R, buf : &mut[u8]) -> Poll<Result<usize>> {
+    let mut b = [0];
+    if self.rend {
+      return Poll::Ready(self.ended());
+    }
+    let mut i = 0;
+    while i < buf.len() {
+      let rr = match Pin::new(&mut *r).poll_read(cx, &mut b[..]) {
+        Poll::Ready(Ok(rr)) => rr,
+        Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+        Poll::Pending => return if i > 0 { Poll::Ready(Ok(i)) } else { Poll::Pending },
+      };
+      if rr == 0 {
+        return Poll::Ready(if i > 0 {
+          Ok(i)
+        } else {
+          Err(Error::new(ErrorKind::UnexpectedEof, "end of stream before end sequence"))
+        });
+      }
+      match self.decode(b[0]) {
+        Ok(Step::Content(c)) => {
+          buf[i] = c;
+          i += 1;
+        },
+        Ok(Step::Skip) => (),
+        Ok(Step::End) => return Poll::Ready(Ok(i)),
+        Err(e) => return Poll::Ready(Err(e)),
+      }
+    }
+    Poll::Ready(Ok(i))
+  }
+
+  /// if end sequence is not read, remaining content is skipped.
+  fn poll_read_end<R : AsyncRead + Unpin>(&mut self, cx : &mut Context, r : &mut R) -> Poll<Result<()>> {
+    let mut buf = [0;64];
+    while !self.rend {
+      try_ready!(self.poll_read_from(cx, r, &mut buf));
+    }
+    self.rend = false;
+    self.aborted = false;
+    Poll::Ready(Ok(()))
+  }
+}
+
+/// escaped content is buffered and written on next call (or on flush or write end), see
+/// `ExtWrite` implementation for errors (pending is returned if no byte could be written).
+#[cfg(feature="async")]
+impl AsyncExtWrite for EscapeTerm {
+  #[inline]
+  fn poll_write_header<W : AsyncWrite + Unpin>(&mut self, _ : &mut Context, _ : &mut W) -> Poll<Result<()>> {
+    Poll::Ready(Ok(()))
+  }
+
+  fn poll_write_into<W : AsyncWrite + Unpin>(&mut self, cx : &mut Context, w : &mut W, cont : &[u8]) -> Poll<Result<usize>> {
+    try_ready!(self.apending.poll_drain(cx, w));
+    let mut escaped = Vec::with_capacity(cont.len());
+    self.conf.escape_into(cont, &mut escaped);
+    self.apending.push(&escaped[..]);
+    match self.apending.poll_drain(cx, w) {
+      Poll::Ready(Err(e)) if self.apending.written() == 0 => {
+        self.apending.clear();
+        Poll::Ready(Err(e))
+      },
+      Poll::Pending if self.apending.written() == 0 => {
+        self.apending.clear();
+        Poll::Pending
+      },
+      // content is accepted, remaining pending is for next call
+      _ => Poll::Ready(Ok(cont.len())),
+    }
+  }
+
+  #[inline]
+  fn poll_flush_into<W : AsyncWrite + Unpin>(&mut self, cx : &mut Context, w : &mut W) -> Poll<Result<()>> {
+    self.apending.poll_drain(cx, w)
+  }
+
+  fn poll_write_end<W : AsyncWrite + Unpin>(&mut self, cx : &mut Context, w : &mut W) -> Poll<Result<()>> {
+    if !self.wend {
+      try_ready!(self.apending.poll_drain(cx, w));
+      let (end, l) = self.conf.end_seq();
+      self.apending.push(&end[..l]);
+      self.wend = true;
+    }
+    try_ready!(self.apending.poll_drain(cx, w));
+    self.wend = false;
+    Poll::Ready(Ok(()))
+  }
+}
+
+
 #[test]
 fn escape_test () {
   let mut et = EscapeTerm::new(0);
@@ -147,3 +614,290 @@ fn escape_test () {
   ).unwrap();
 }
 
+#[test]
+fn escape_reuse_test () {
+  let mut et = EscapeTerm::new(0);
+  let mut et2 = EscapeTerm::new(0);
+  for _ in 0..3 {
+    test_bytes_wr(
+      150,
+      7,
+      &mut et,
+      &mut et2,
+    ).unwrap();
+  }
+  check_symmetry(&Default::default(), &mut et, &mut et2).unwrap();
+  let mut et = EscapeTerm::new(1);
+  let mut et2 = EscapeTerm::new(1);
+  check_symmetry(&Default::default(), &mut et, &mut et2).unwrap();
+}
+
+#[test]
+fn escape_transports_test () {
+  check_ext(|| EscapeTerm::new(0), || EscapeTerm::new(0), false).unwrap();
+  check_ext(|| EscapeTerm::new(3), || EscapeTerm::new(3), false).unwrap();
+}
+
+#[test]
+fn escape_write_error_test () {
+  let mut et = EscapeTerm::new(1);
+  // every second write fails
+  let mut w = FaultWrite::new(Vec::new(), ErrorKind::Other, 2);
+  assert!(et.write_into(&mut w, &[2]).unwrap() == 1);
+  // content is not accepted if none of it is written
+  assert!(et.write_into(&mut w, &[3]).is_err());
+  assert!(et.write_into(&mut w, &[3]).unwrap() == 1);
+  assert!(et.write_end(&mut w).is_err());
+  et.write_end(&mut w).unwrap();
+  assert!(&w.inner[..] == &[2,3,1,0][..]);
+}
+
+/// async writer failing on first write
+#[cfg(all(test,feature="async"))]
+struct FailOnce(Vec<u8>, bool);
+
+#[cfg(all(test,feature="async"))]
+impl AsyncWrite for FailOnce {
+  fn poll_write(mut self : Pin<&mut Self>, _ : &mut Context, buf : &[u8]) -> Poll<Result<usize>> {
+    if !self.1 {
+      self.1 = true;
+      return Poll::Ready(Err(Error::new(ErrorKind::Other, "first write failure")));
+    }
+    self.0.extend_from_slice(buf);
+    Poll::Ready(Ok(buf.len()))
+  }
+  fn poll_flush(self : Pin<&mut Self>, _ : &mut Context) -> Poll<Result<()>> {
+    Poll::Ready(Ok(()))
+  }
+  fn poll_close(self : Pin<&mut Self>, _ : &mut Context) -> Poll<Result<()>> {
+    Poll::Ready(Ok(()))
+  }
+}
+
+#[cfg(feature="async")]
+#[test]
+fn escape_async_write_error_test () {
+  let mut et = EscapeTerm::new(1);
+  let mut w = FailOnce(Vec::new(), false);
+  // content is not accepted if none of it is written
+  assert!(block_poll(|cx| et.poll_write_into(cx, &mut w, &[2,3])).is_err());
+  assert!(block_poll(|cx| et.poll_write_into(cx, &mut w, &[2,3])).unwrap() == 2);
+  block_poll(|cx| et.poll_write_end(cx, &mut w)).unwrap();
+  assert!(&w.0[..] == &[2,3,1,0][..]);
+}
+
+#[cfg(feature="async")]
+#[test]
+fn escape_async_read_end_test () {
+  let mut et = EscapeTerm::new(1);
+  let mut r = PendingIo::new(Cursor::new(vec![2,3,1,0,4,1,0]), 1);
+  let mut buf = [0;4];
+  assert!(block_poll(|cx| et.poll_read_from(cx, &mut r, &mut buf[..1])).unwrap() == 1);
+  // rest of message is skipped
+  block_poll(|cx| et.poll_read_end(cx, &mut r)).unwrap();
+  assert!(block_poll(|cx| et.poll_read_from(cx, &mut r, &mut buf)).unwrap() == 1);
+  assert!(buf[0] == 4);
+  assert!(block_poll(|cx| et.poll_read_from(cx, &mut r, &mut buf)).unwrap() == 0);
+  block_poll(|cx| et.poll_read_end(cx, &mut r)).unwrap();
+}
+
+#[test]
+fn escape_both_directions_test () {
+  // same instance reading and writing
+  let mut et = EscapeTerm::with_config(EscapeConfig::new(1).with_abort(5)).unwrap();
+  let mut input = &[2, 1, 0][..];
+  let mut buf = [0;4];
+  assert!(et.read_from(&mut input, &mut buf).unwrap() == 1);
+  // end is read (read end not called) while writing a message
+  let mut out = Vec::new();
+  et.write_into(&mut out, &[3]).unwrap();
+  et.write_end(&mut out).unwrap();
+  et.write_into(&mut out, &[4]).unwrap();
+  et.write_abort(&mut out).unwrap();
+  assert!(&out[..] == &[3,1,0,4,1,5][..]);
+  assert!(et.read_from(&mut input, &mut buf).unwrap() == 0);
+  et.read_end(&mut input).unwrap();
+}
+
+#[test]
+fn escape_truncated_test () {
+  let mut et = EscapeTerm::new(1);
+  let mut buf = [0;8];
+  // truncated escape sequence
+  let mut input = &[3, 1][..];
+  assert!(et.read_from(&mut input, &mut buf).unwrap() == 1);
+  assert!(et.read_from(&mut input, &mut buf).unwrap_err().kind() == ErrorKind::UnexpectedEof);
+  // transport writing nothing
+  let mut out = [0;1];
+  let mut w = &mut out[..];
+  et.write_into(&mut w, &[2, 2]).unwrap();
+  assert!(et.write_end(&mut w).unwrap_err().kind() == ErrorKind::WriteZero);
+}
+
+#[test]
+fn buffered_test () {
+  // same wire format (no read ahead with a one byte buffer : byte after end is read raw)
+  let mut et = EscapeTerm::new(0);
+  let mut et2 = BufEscapeTerm::with_capacity(0, 1);
+  test_bytes_wr(
+    150,
+    7,
+    &mut et,
+    &mut et2,
+  ).unwrap();
+  let mut et = BufEscapeTerm::new(3);
+  let mut et2 = EscapeTerm::new(3);
+  test_bytes_wr(
+    150,
+    200,
+    &mut et,
+    &mut et2,
+  ).unwrap();
+  // lookahead is kept between messages
+  let conf = SymmetryConfig {
+    raw_content : false,
+    .. SymmetryConfig::default()
+  };
+  check_symmetry(&conf, &mut BufEscapeTerm::new(0), &mut BufEscapeTerm::with_capacity(0, 33)).unwrap();
+  check_symmetry(&conf, &mut EscapeTerm::new(1), &mut BufEscapeTerm::new(1)).unwrap();
+  check_transports(|| EscapeTerm::new(0), || BufEscapeTerm::with_capacity(0, 5), false).unwrap();
+  check_transports(|| BufEscapeTerm::new(3), || BufEscapeTerm::new(3), false).unwrap();
+}
+
+#[test]
+fn buffered_give_back_test () {
+  let mut et = EscapeTerm::new(1);
+  let mut w = Vec::new();
+  et.write_header(&mut w).unwrap();
+  et.write_all_into(&mut w, &[1, 2, 1, 3]).unwrap();
+  et.write_end(&mut w).unwrap();
+  // raw content for next reader
+  w.extend_from_slice(&[7, 1, 8]);
+  let mut input = &w[..];
+  let mut br = BufEscapeTerm::new(1);
+  let mut buf = [0;16];
+  br.read_header(&mut input).unwrap();
+  assert!(br.read_from(&mut input, &mut buf).unwrap() == 4);
+  assert!(&buf[..4] == &[1, 2, 1, 3]);
+  assert!(br.read_from(&mut input, &mut buf).unwrap() == 0);
+  br.read_end(&mut input).unwrap();
+  // whole stream was read ahead
+  assert!(input.is_empty());
+  assert!(br.lookahead() == &[7, 1, 8]);
+  let mut rest = Vec::new();
+  br.give_back(&mut input).read_to_end(&mut rest).unwrap();
+  assert!(rest == vec![7, 1, 8]);
+  assert!(br.lookahead().is_empty());
+}
+
+#[test]
+fn escape_config_test () {
+  // rfc 1055 encoding
+  let mut et = EscapeTerm::with_config(EscapeConfig::slip()).unwrap();
+  let mut w = Vec::new();
+  et.write_all_into(&mut w, &[0xC0, 0xDB, 1]).unwrap();
+  et.write_end(&mut w).unwrap();
+  assert!(w == vec![0xDB, 0xDC, 0xDB, 0xDD, 1, 0xC0]);
+  // no zero before delimiter
+  let mut et = EscapeTerm::with_config(EscapeConfig::zero_delimited()).unwrap();
+  let mut w = Vec::new();
+  et.write_all_into(&mut w, &[0, 0x7D, 0, 1]).unwrap();
+  et.write_end(&mut w).unwrap();
+  assert!(w == vec![0x7D, 0x20, 0x7D, 0x5D, 0x7D, 0x20, 1, 0]);
+  for conf in [EscapeConfig::slip(), EscapeConfig::zero_delimited(), EscapeConfig::new(0).with_keepalive(2).with_abort(3)].iter() {
+    check_ext(|| EscapeTerm::with_config(*conf).unwrap(), || EscapeTerm::with_config(*conf).unwrap(), false).unwrap();
+    let mut et = EscapeTerm::with_config(*conf).unwrap();
+    let sconf = SymmetryConfig {
+      raw_content : false,
+      .. SymmetryConfig::default()
+    };
+    check_symmetry(&sconf, &mut et, &mut BufEscapeTerm::with_config(*conf, 17).unwrap()).unwrap();
+    check_transports(|| EscapeTerm::with_config(*conf).unwrap(), || BufEscapeTerm::with_config(*conf, 5).unwrap(), false).unwrap();
+  }
+  // invalid configurations
+  assert!(EscapeTerm::with_config(EscapeConfig::new(0).with_keepalive(0)).is_err());
+  assert!(EscapeTerm::with_config(EscapeConfig::new(0).with_keepalive(2).with_abort(2)).is_err());
+  let mut conf = EscapeConfig::slip();
+  conf.term = Terminator::Raw { end : 0xDB, code : 0xDC };
+  assert!(EscapeTerm::with_config(conf).is_err());
+}
+
+#[test]
+fn escape_control_test () {
+  let conf = EscapeConfig::slip().with_keepalive(0x01).with_abort(0x02);
+  let mut et = EscapeTerm::with_config(conf).unwrap();
+  let mut w = Vec::new();
+  et.write_keepalive(&mut w).unwrap();
+  et.write_all_into(&mut w, &[5, 6]).unwrap();
+  et.write_abort(&mut w).unwrap();
+  et.write_all_into(&mut w, &[7]).unwrap();
+  et.write_keepalive(&mut w).unwrap();
+  et.write_all_into(&mut w, &[8]).unwrap();
+  et.write_end(&mut w).unwrap();
+  // not configured
+  assert!(EscapeTerm::new(0).write_keepalive(&mut w).unwrap_err().kind() == ErrorKind::InvalidInput);
+  read_control(&w[..], &mut EscapeTerm::with_config(conf).unwrap());
+  read_control(&w[..], &mut BufEscapeTerm::with_config(conf, 3).unwrap());
+}
+
+#[cfg(test)]
+fn read_control<ER : ExtRead>(mut input : &[u8], br : &mut ER) {
+  let mut buf = [0;8];
+  let mut r = 0;
+  let res = loop {
+    match br.read_from(&mut input, &mut buf[r..]) {
+      Ok(n) if n > 0 => r += n,
+      res => break res,
+    }
+  };
+  assert!(res.unwrap_err().kind() == ErrorKind::ConnectionAborted);
+  // aborted until read end
+  assert!(br.read_from(&mut input, &mut buf).unwrap_err().kind() == ErrorKind::ConnectionAborted);
+  br.read_end(&mut input).unwrap();
+  let mut r = 0;
+  loop {
+    match br.read_from(&mut input, &mut buf[r..]).unwrap() {
+      0 => break,
+      n => r += n,
+    }
+  }
+  assert!(&buf[..r] == &[7, 8]);
+  br.read_end(&mut input).unwrap();
+}
+
+#[cfg(feature="async")]
+#[test]
+fn escape_async_test () {
+  let mut et = EscapeTerm::new(0);
+  let mut et2 = EscapeTerm::new(0);
+  test_bytes_wr_async(
+    150,
+    7,
+    &mut et,
+    &mut et2,
+  ).unwrap();
+  let mut et = EscapeTerm::new(3);
+  let mut et2 = EscapeTerm::new(3);
+  test_bytes_wr_async(
+    150,
+    200,
+    &mut et,
+    &mut et2,
+  ).unwrap();
+  let mut et = EscapeTerm::new(1);
+  let mut et2 = EscapeTerm::new(1);
+  test_bytes_w_sync_r_async(
+    150,
+    15,
+    &mut et,
+    &mut et2,
+  ).unwrap();
+  let mut et = EscapeTerm::new(1);
+  let mut et2 = EscapeTerm::new(1);
+  test_bytes_w_async_r_sync(
+    150,
+    15,
+    &mut et,
+    &mut et2,
+  ).unwrap();
+}
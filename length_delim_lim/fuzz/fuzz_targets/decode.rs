@@ -0,0 +1,55 @@
+//! Decode arbitrary input : LengthDelim read must return an error or a result, never panic or
+//! loop.
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate readwrite_comp;
+extern crate length_delim_lim;
+
+use std::io::Cursor;
+use readwrite_comp::ExtRead;
+use length_delim_lim::{
+  LengthDelim,
+  LengthFormat,
+};
+
+fn decode<ER : ExtRead>(data : &[u8], er : &mut ER) {
+  let mut input = Cursor::new(data);
+  let mut buf = [0;32];
+  // at most one message per input byte
+  for _ in 0..data.len() + 1 {
+    if er.read_header(&mut input).is_err() {
+      return;
+    }
+    loop {
+      match er.read_from(&mut input, &mut buf) {
+        Ok(0) => break,
+        Ok(_) => (),
+        Err(_) => return,
+      }
+    }
+    if er.read_end(&mut input).is_err() {
+      return;
+    }
+    if input.position() as usize == data.len() {
+      return;
+    }
+  }
+}
+
+const FORMATS : [LengthFormat;5] = [
+  LengthFormat::Varint,
+  LengthFormat::U16Be,
+  LengthFormat::U16Le,
+  LengthFormat::U32Be,
+  LengthFormat::U32Le,
+];
+
+fuzz_target!(|data: &[u8]| {
+  if data.is_empty() {
+    return;
+  }
+  // first byte is format and max frame size
+  let format = FORMATS[data[0] as usize % FORMATS.len()];
+  decode(&data[1..], &mut LengthDelim::new(format, data[0] as usize * 4));
+});
@@ -0,0 +1,332 @@
+//! Length delimited limiter : each message is a single frame, its content prefixed by its
+//! length.
+//! Length is either a LEB128 varint (protobuf length delimited format) or a fixed size u16 or u32
+//! (big or little endian, u32 big endian being the default format of tokio
+//! `LengthDelimitedCodec`). Length does not include the prefix.
+//!
+//! Frame length must be known before writing it, so content is buffered until `write_end` (flush
+//! does not write anything), max frame size bounds this buffer.
+
+extern crate readwrite_comp;
+extern crate readwrite_comp_test;
+extern crate byteorder;
+
+use std::io::{
+  Write,
+  Read,
+  Result,
+  Error,
+  ErrorKind,
+  IoSlice,
+};
+use readwrite_comp::{
+  ExtRead,
+  ExtWrite,
+  write_all_from,
+  read_exact_from,
+  read_in_frame,
+  skip_in_frame,
+};
+use byteorder::{
+  ByteOrder,
+  BigEndian,
+  LittleEndian,
+};
+#[cfg(test)]
+use readwrite_comp_test::check_ext;
+
+/// max length of a prefix (u64 varint)
+const MAX_PREFIX : usize = 10;
+
+/// default max frame size (same as tokio `LengthDelimitedCodec`)
+pub const DEFAULT_MAX_FRAME : usize = 8 * 1024 * 1024;
+
+/// Length prefix encoding
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum LengthFormat {
+  /// LEB128 unsigned varint
+  Varint,
+  U16Be,
+  U16Le,
+  U32Be,
+  U32Le,
+}
+
+impl LengthFormat {
+  /// biggest length encodable
+  pub fn max_len(&self) -> u64 {
+    match *self {
+      LengthFormat::Varint => u64::MAX,
+      LengthFormat::U16Be | LengthFormat::U16Le => u16::MAX as u64,
+      LengthFormat::U32Be | LengthFormat::U32Le => u32::MAX as u64,
+    }
+  }
+
+  /// encode length in `buf`, return encoded length
+  fn encode(&self, len : u64, buf : &mut [u8;MAX_PREFIX]) -> usize {
+    match *self {
+      LengthFormat::Varint => {
+        let mut v = len;
+        let mut i = 0;
+        while v >= 0x80 {
+          buf[i] = (v as u8) | 0x80;
+          v >>= 7;
+          i += 1;
+        }
+        buf[i] = v as u8;
+        i + 1
+      },
+      LengthFormat::U16Be => { BigEndian::write_u16(&mut buf[..], len as u16); 2 },
+      LengthFormat::U16Le => { LittleEndian::write_u16(&mut buf[..], len as u16); 2 },
+      LengthFormat::U32Be => { BigEndian::write_u32(&mut buf[..], len as u32); 4 },
+      LengthFormat::U32Le => { LittleEndian::write_u32(&mut buf[..], len as u32); 4 },
+    }
+  }
+
+  /// decode length if `buf` contains a complete prefix
+  fn decode(&self, buf : &[u8]) -> Result<Option<u64>> {
+    let l = match *self {
+      LengthFormat::Varint => {
+        let last = buf.len() - 1;
+        if buf[last] & 0x80 != 0 {
+          if buf.len() == MAX_PREFIX {
+            return Err(Error::new(ErrorKind::InvalidData, "varint length too long"));
+          }
+          return Ok(None);
+        }
+        if last == MAX_PREFIX - 1 && buf[last] > 1 {
+          return Err(Error::new(ErrorKind::InvalidData, "varint length overflow"));
+        }
+        return Ok(Some(buf.iter().enumerate().fold(0, |acc, (i, b)| acc | (((b & 0x7F) as u64) << (7 * i)))));
+      },
+      LengthFormat::U16Be | LengthFormat::U16Le => 2,
+      LengthFormat::U32Be | LengthFormat::U32Le => 4,
+    };
+    if buf.len() < l {
+      return Ok(None);
+    }
+    Ok(Some(match *self {
+      LengthFormat::U16Be => BigEndian::read_u16(buf) as u64,
+      LengthFormat::U16Le => LittleEndian::read_u16(buf) as u64,
+      LengthFormat::U32Be => BigEndian::read_u32(buf) as u64,
+      LengthFormat::U32Le => LittleEndian::read_u32(buf) as u64,
+      LengthFormat::Varint => unreachable!(),
+    }))
+  }
+}
+
+pub struct LengthDelim {
+  format : LengthFormat,
+  /// max frame content length (for both read and write)
+  max_frame : usize,
+  /// prefix space followed by frame content
+  wbuf : Vec<u8>,
+  /// written bytes of wbuf (frame is being written if not 0)
+  wpos : usize,
+  /// read prefix bytes
+  hbuf : [u8;MAX_PREFIX],
+  /// number of read prefix bytes
+  hlen : usize,
+  /// if prefix is read
+  hread : bool,
+  /// remaining frame content to read
+  rem : usize,
+}
+
+impl LengthDelim {
+  /// `max_frame` is bounded by max length of `format`.
+  pub fn new(format : LengthFormat, max_frame : usize) -> Self {
+    let max_frame = if max_frame as u64 > format.max_len() { format.max_len() as usize } else { max_frame };
+    LengthDelim {
+      format,
+      max_frame,
+      wbuf : vec![0;MAX_PREFIX],
+      wpos : 0,
+      hbuf : [0;MAX_PREFIX],
+      hlen : 0,
+      hread : false,
+      rem : 0,
+    }
+  }
+
+  /// protobuf length delimited messages (varint prefix)
+  pub fn protobuf() -> Self {
+    Self::new(LengthFormat::Varint, DEFAULT_MAX_FRAME)
+  }
+
+  /// tokio `LengthDelimitedCodec` default (u32 big endian prefix, 8MB max frame)
+  pub fn tokio() -> Self {
+    Self::new(LengthFormat::U32Be, DEFAULT_MAX_FRAME)
+  }
+
+  #[inline]
+  pub fn format(&self) -> LengthFormat {
+    self.format
+  }
+
+  #[inline]
+  pub fn max_frame(&self) -> usize {
+    self.max_frame
+  }
+
+  /// read prefix, progress is kept on transport error
+  fn read_len<R : Read>(&mut self, r : &mut R) -> Result<()> {
+    while !self.hread {
+      // byte per byte (prefix length is unknown)
+      let next = self.hlen + 1;
+      try!(read_exact_from(r, &mut self.hbuf[..next], &mut self.hlen));
+      match self.format.decode(&self.hbuf[..self.hlen]) {
+        Ok(None) => (),
+        Ok(Some(l)) => {
+          self.hlen = 0;
+          if l > self.max_frame as u64 {
+            return Err(Error::new(ErrorKind::InvalidData, "frame bigger than max frame size"));
+          }
+          self.rem = l as usize;
+          self.hread = true;
+        },
+        Err(e) => {
+          self.hlen = 0;
+          return Err(e);
+        },
+      }
+    }
+    Ok(())
+  }
+}
+
+impl ExtRead for LengthDelim {
+  fn read_header<R : Read>(&mut self, r : &mut R) -> Result<()> {
+    self.read_len(r)
+  }
+
+  /// return 0 at end of frame.
+  fn read_from<R : Read>(&mut self, r : &mut R, buf : &mut[u8]) -> Result<usize> {
+    try!(self.read_len(r));
+    read_in_frame(r, &mut self.rem, buf)
+  }
+
+  /// frame content is dropped without copy in caller buffer.
+  fn skip_from<R : Read>(&mut self, r : &mut R, n : usize) -> Result<usize> {
+    try!(self.read_len(r));
+    skip_in_frame(r, &mut self.rem, n)
+  }
+
+  /// remaining frame content is skipped.
+  fn read_end<R : Read>(&mut self, r : &mut R) -> Result<()> {
+    try!(self.discard_to_end(r));
+    self.hread = false;
+    Ok(())
+  }
+}
+
+/// Frame is written on `write_end`.
+impl ExtWrite for LengthDelim {
+  #[inline]
+  fn write_header<W : Write>(&mut self, _ : &mut W) -> Result<()> {
+    Ok(())
+  }
+
+  /// error if frame would be bigger than max frame size (content is not written).
+  fn write_into<W : Write>(&mut self, _ : &mut W, cont : &[u8]) -> Result<usize> {
+    if self.wbuf.len() - MAX_PREFIX + cont.len() > self.max_frame {
+      return Err(Error::new(ErrorKind::InvalidInput, "frame bigger than max frame size"));
+    }
+    self.wbuf.extend_from_slice(cont);
+    Ok(cont.len())
+  }
+
+  /// all buffers are added to the frame.
+  fn write_vectored_into<W : Write>(&mut self, _ : &mut W, bufs : &[IoSlice]) -> Result<usize> {
+    let len = bufs.iter().fold(0, |l, b| l + b.len());
+    if self.wbuf.len() - MAX_PREFIX + len > self.max_frame {
+      return Err(Error::new(ErrorKind::InvalidInput, "frame bigger than max frame size"));
+    }
+    for b in bufs.iter() {
+      self.wbuf.extend_from_slice(b);
+    }
+    Ok(len)
+  }
+
+  /// no effect : frame is written on `write_end`
+  #[inline]
+  fn flush_into<W : Write>(&mut self, _ : &mut W) -> Result<()> {
+    Ok(())
+  }
+
+  fn write_end<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    if self.wpos == 0 {
+      let mut prefix = [0;MAX_PREFIX];
+      let l = self.format.encode((self.wbuf.len() - MAX_PREFIX) as u64, &mut prefix);
+      self.wpos = MAX_PREFIX - l;
+      self.wbuf[self.wpos..MAX_PREFIX].copy_from_slice(&prefix[..l]);
+    }
+    try!(write_all_from(w, &self.wbuf, &mut self.wpos));
+    self.wbuf.truncate(MAX_PREFIX);
+    self.wpos = 0;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+const FORMATS : [LengthFormat;5] = [
+  LengthFormat::Varint,
+  LengthFormat::U16Be,
+  LengthFormat::U16Le,
+  LengthFormat::U32Be,
+  LengthFormat::U32Le,
+];
+
+#[cfg(test)]
+fn frame(ld : &mut LengthDelim, cont : &[u8]) -> Result<Vec<u8>> {
+  let mut w = Vec::new();
+  try!(ld.write_all_into(&mut w, cont));
+  try!(ld.write_end(&mut w));
+  Ok(w)
+}
+
+#[test]
+fn length_delim_test () {
+  for f in FORMATS.iter() {
+    check_ext(|| LengthDelim::new(*f, 1000), || LengthDelim::new(*f, 1000), false).unwrap();
+  }
+}
+
+#[test]
+fn length_delim_format_test () {
+  // protobuf varint
+  let cont = vec![1;300];
+  let w = frame(&mut LengthDelim::protobuf(), &cont[..]).unwrap();
+  assert!(&w[..2] == &[0xAC, 0x02]);
+  assert!(&w[2..] == &cont[..]);
+  assert!(frame(&mut LengthDelim::protobuf(), &[]).unwrap() == vec![0]);
+  // tokio
+  let w = frame(&mut LengthDelim::tokio(), b"hello").unwrap();
+  assert!(&w[..] == b"\x00\x00\x00\x05hello");
+  let w = frame(&mut LengthDelim::new(LengthFormat::U16Le, 10), b"hello").unwrap();
+  assert!(&w[..] == b"\x05\x00hello");
+  // varint over u64
+  let mut input = &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x02][..];
+  let mut ld = LengthDelim::new(LengthFormat::Varint, usize::MAX);
+  assert!(ld.read_header(&mut input).unwrap_err().kind() == ErrorKind::InvalidData);
+}
+
+#[test]
+fn length_delim_max_test () {
+  let mut ld = LengthDelim::new(LengthFormat::Varint, 10);
+  let mut w = Vec::new();
+  assert!(ld.write_into(&mut w, &[1;8]).unwrap() == 8);
+  assert!(ld.write_into(&mut w, &[1;3]).unwrap_err().kind() == ErrorKind::InvalidInput);
+  ld.write_end(&mut w).unwrap();
+  // vectored
+  let mut w = Vec::new();
+  assert!(ld.write_vectored_into(&mut w, &[IoSlice::new(&[1;8]), IoSlice::new(&[2;3])]).unwrap_err().kind() == ErrorKind::InvalidInput);
+  assert!(ld.write_vectored_into(&mut w, &[IoSlice::new(&[1;8]), IoSlice::new(&[2;2])]).unwrap() == 10);
+  ld.write_end(&mut w).unwrap();
+  assert!(&w[..] == &[10, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2]);
+  let w = frame(&mut LengthDelim::new(LengthFormat::Varint, 11), &[1;11]).unwrap();
+  let mut ld = LengthDelim::new(LengthFormat::Varint, 10);
+  assert!(ld.read_header(&mut &w[..]).unwrap_err().kind() == ErrorKind::InvalidData);
+  // bounded by format
+  assert!(LengthDelim::new(LengthFormat::U16Be, usize::MAX).max_frame() == 0xFFFF);
+}
@@ -0,0 +1,201 @@
+//! Shared tests for async ext implementations, using an in memory transport returning pending
+//! every other call (to test resumable implementation).
+
+use readwrite_comp::{
+  ExtWrite,
+  ExtRead,
+  AsyncExtWrite,
+  AsyncExtRead,
+};
+
+use std::io::{
+  Write,
+  Read,
+  Cursor,
+  Result,
+};
+use std::pin::Pin;
+use std::ptr;
+use std::task::{
+  Context,
+  Poll,
+  RawWaker,
+  RawWakerVTable,
+  Waker,
+};
+use futures_io::{
+  AsyncRead,
+  AsyncWrite,
+};
+
+use rand::RngCore;
+use rand::rngs::OsRng;
+
+use super::{
+  write_bytes,
+  read_bytes,
+};
+
+/// max number of consecutive pending before considering that a poll function does not progress
+const MAX_PENDING : usize = 1000;
+
+static NOOP_WAKER_VTABLE : RawWakerVTable = RawWakerVTable::new(noop_clone, noop, noop, noop);
+
+unsafe fn noop_clone(_ : *const ()) -> RawWaker {
+  RawWaker::new(ptr::null(), &NOOP_WAKER_VTABLE)
+}
+
+unsafe fn noop(_ : *const ()) {}
+
+/// poll until ready (busy loop with a no op waker).
+/// Panic if the poll function keep returning pending.
+pub fn block_poll<T, F : FnMut(&mut Context) -> Poll<T>>(mut f : F) -> T {
+  let waker = unsafe { Waker::from_raw(RawWaker::new(ptr::null(), &NOOP_WAKER_VTABLE)) };
+  let mut cx = Context::from_waker(&waker);
+  let mut nb_pending = 0;
+  loop {
+    match f(&mut cx) {
+      Poll::Ready(t) => return t,
+      Poll::Pending => {
+        nb_pending += 1;
+        assert!(nb_pending < MAX_PENDING, "poll function does not progress");
+      },
+    }
+  }
+}
+
+/// In memory async transport : every other call return pending (waking immediately) and at most
+/// `chunk` bytes are transfered per call.
+pub struct PendingIo {
+  pub inner : Cursor<Vec<u8>>,
+  chunk : usize,
+  pending : bool,
+}
+
+impl PendingIo {
+  pub fn new(inner : Cursor<Vec<u8>>, chunk : usize) -> Self {
+    PendingIo {
+      inner,
+      chunk,
+      pending : true,
+    }
+  }
+  #[inline]
+  fn switch_pending(&mut self, cx : &mut Context) -> bool {
+    self.pending = !self.pending;
+    if !self.pending {
+      cx.waker().wake_by_ref();
+    }
+    !self.pending
+  }
+}
+
+impl AsyncWrite for PendingIo {
+  fn poll_write(self : Pin<&mut Self>, cx : &mut Context, buf : &[u8]) -> Poll<Result<usize>> {
+    let this = self.get_mut();
+    if this.switch_pending(cx) {
+      return Poll::Pending;
+    }
+    let l = if buf.len() > this.chunk { this.chunk } else { buf.len() };
+    Poll::Ready(this.inner.write(&buf[..l]))
+  }
+  fn poll_flush(self : Pin<&mut Self>, _ : &mut Context) -> Poll<Result<()>> {
+    Poll::Ready(Ok(()))
+  }
+  fn poll_close(self : Pin<&mut Self>, _ : &mut Context) -> Poll<Result<()>> {
+    Poll::Ready(Ok(()))
+  }
+}
+
+impl AsyncRead for PendingIo {
+  fn poll_read(self : Pin<&mut Self>, cx : &mut Context, buf : &mut [u8]) -> Poll<Result<usize>> {
+    let this = self.get_mut();
+    if this.switch_pending(cx) {
+      return Poll::Pending;
+    }
+    let l = if buf.len() > this.chunk { this.chunk } else { buf.len() };
+    Poll::Ready(this.inner.read(&mut buf[..l]))
+  }
+}
+
+/// same as `test_bytes_wr` with async write and async read
+pub fn test_bytes_wr_async<BW : AsyncExtWrite, BR : AsyncExtRead>
+  (inp_length : usize, buf_length : usize, bw : &mut BW, br : &mut BR) -> Result<()> {
+  let mut bufb = vec![0;buf_length];
+  let (output, reference, endcontent) = try!(write_bytes_async(inp_length, &mut bufb, bw));
+  read_bytes_async(output, &reference, endcontent, inp_length, &mut bufb, br)
+}
+
+/// same as `test_bytes_wr` with sync write and async read (check wire format compatibility)
+pub fn test_bytes_w_sync_r_async<BW : ExtWrite, BR : AsyncExtRead>
+  (inp_length : usize, buf_length : usize, bw : &mut BW, br : &mut BR) -> Result<()> {
+  let mut bufb = vec![0;buf_length];
+  let (output, reference, endcontent) = try!(write_bytes(inp_length, &mut bufb, bw));
+  read_bytes_async(output, &reference, endcontent, inp_length, &mut bufb, br)
+}
+
+/// same as `test_bytes_wr` with async write and sync read (check wire format compatibility)
+pub fn test_bytes_w_async_r_sync<BW : AsyncExtWrite, BR : ExtRead>
+  (inp_length : usize, buf_length : usize, bw : &mut BW, br : &mut BR) -> Result<()> {
+  let mut bufb = vec![0;buf_length];
+  let (mut output, reference, endcontent) = try!(write_bytes_async(inp_length, &mut bufb, bw));
+  read_bytes(&mut output, &reference, endcontent, inp_length, &mut bufb, br)
+}
+
+fn write_bytes_async<BW : AsyncExtWrite>
+  (inp_length : usize, buf : &mut [u8], bw : &mut BW) -> Result<(Cursor<Vec<u8>>, Vec<u8>, u8)> {
+  let mut rng = OsRng;
+  let mut output = PendingIo::new(Cursor::new(Vec::new()), 3);
+  let mut reference = Vec::new();
+  let mut has_started = false;
+  let mut i = 0;
+  while inp_length > i {
+    rng.fill_bytes(buf);
+    if !has_started {
+      try!(block_poll(|cx| bw.poll_write_header(cx, &mut output)));
+      has_started = true;
+    };
+    let l = if inp_length - i < buf.len() { inp_length - i } else { buf.len() };
+    let ww = try!(block_poll(|cx| bw.poll_write_into(cx, &mut output, &buf[..l])));
+    assert!(ww != 0);
+    reference.extend_from_slice(&buf[..ww]);
+    i += ww;
+  }
+  try!(block_poll(|cx| bw.poll_write_end(cx, &mut output)));
+  try!(block_poll(|cx| bw.poll_flush_into(cx, &mut output)));
+  // write next content
+  rng.fill_bytes(buf);
+  let endcontent = buf[0];
+  let mut output = output.inner;
+  try!(output.write(&buf[..1]));
+  output.set_position(0);
+  Ok((output, reference, endcontent))
+}
+
+fn read_bytes_async<BR : AsyncExtRead>
+  (input : Cursor<Vec<u8>>, reference : &[u8], endcontent : u8, inp_length : usize, buf : &mut [u8], br : &mut BR) -> Result<()> {
+  let mut input = PendingIo::new(input, 3);
+  try!(block_poll(|cx| br.poll_read_header(cx, &mut input)));
+  let mut rr = 1;
+  let mut i = 0;
+  while rr != 0 {
+    rr = try!(block_poll(|cx| br.poll_read_from(cx, &mut input, buf)));
+    if rr != 0 {
+      if i + rr > inp_length {
+        if inp_length > i {
+          let padstart = inp_length - i;
+          assert!(buf[..padstart] == reference[i..]);
+        } // else the window is bigger than buffer and padding is being read
+      } else {
+        assert!(buf[..rr] == reference[i..i + rr]);
+      }
+    }
+    i += rr;
+  }
+  try!(block_poll(|cx| br.poll_read_end(cx, &mut input)));
+  assert!(i >= inp_length);
+  let ni = try!(input.inner.read(buf));
+  assert!(ni == 1);
+  assert!(endcontent == buf[0]);
+  Ok(())
+}
@@ -0,0 +1,359 @@
+//! Test layers of readwrite-comp unit tests (`EndStream` and `Ciph`) written to support
+//! `check_transports` : short writes and reads, progress kept on io error, ends not done twice.
+//! They are used to check readwrite-comp compositions (`CompExtW`, `MultiWExt`...) over
+//! transports.
+
+use readwrite_comp::{
+  ExtWrite,
+  ExtRead,
+};
+
+use std::io;
+use std::io::{
+  Write,
+  Read,
+  Result,
+  Error,
+  ErrorKind,
+};
+use std::num::Wrapping;
+
+/// a writer with end byte usage (we read content and need to stop at some point without knowing
+/// what is in content). After N read byte if 0 end if 1 continu for N next bytes.
+/// First is window size (renewable with padding if flushed or end).
+/// Second is counter of byte written for this window.
+/// Third is read ended (terminal 0 read), fourth is write ended (terminal 0 written) : ends are
+/// not done twice (outer layer end may be called again on error).
+/// This is only for testing, very redundant with 'sized_window_lim' crates.
+#[derive(Clone)]
+pub struct EndStream(usize,usize,bool,bool);
+
+impl EndStream {
+  pub fn new(winsize : usize) -> Self { EndStream(winsize, winsize, false, false) }
+
+  /// read byte after a full window : 0 ends the message, otherwhise a new window starts.
+  /// It is read on next call so that window content is not lost on error.
+  fn read_terminal<R : Read>(&mut self, r : &mut R) -> Result<()> {
+    if self.1 != 0 || self.2 {
+      return Ok(());
+    }
+    let mut b = [0];
+    if let Err(e) = r.read_exact(&mut b) {
+      return Err(if e.kind() == ErrorKind::UnexpectedEof {
+        Error::new(ErrorKind::UnexpectedEof, "No bytes after window size, do not know if ended or repeat")
+      } else {
+        e
+      });
+    }
+    if b[0] != 0 {
+      // read next window
+      self.1 = self.0;
+    } else {
+      // ended window, need header for next (stuck to ret 0 up to next header)
+      // the point of this write : getting a read at 0 at some point for unknow content read (for
+      // instance encyphered bytes).
+      self.2 = true;
+    }
+    Ok(())
+  }
+}
+
+impl ExtWrite for EndStream {
+  #[inline]
+  fn write_header<W : Write>(&mut self, _ : &mut W) -> Result<()> {
+    self.1 = self.0;
+    self.3 = false;
+    Ok(())
+  }
+  #[inline]
+  fn write_into<W : Write>(&mut self, w : &mut W, cont : &[u8]) -> Result<usize> {
+    let mut ix = 0;
+    while ix < cont.len() {
+
+      let res = if self.1 == 0 {
+        // non 0 (terminal) value
+        w.write_all(&[1]).map(|_| {
+          self.1 = self.0;
+          0
+        })
+      } else {
+        let end = if self.1 + ix < cont.len() { ix + self.1 } else { cont.len() };
+        match w.write(&cont[ix..end]) {
+          Ok(0) => Err(Error::new(ErrorKind::WriteZero, "Could not write window content")),
+          r => r,
+        }
+      };
+      match res {
+        Ok(l) => {
+          ix += l;
+          self.1 -= l;
+        },
+        Err(ref e) if e.kind() == ErrorKind::Interrupted => (),
+        // written content is reported, error will be returned on next call
+        Err(_) if ix > 0 => return Ok(ix),
+        Err(e) => return Err(e),
+      }
+    };
+    Ok(ix)
+  }
+
+  #[inline]
+  fn write_end<W : Write>(&mut self, r : &mut W) -> Result<()> {
+    if self.3 {
+      return Ok(());
+    }
+    // padd with 2 for easier frame read (0 stop 1 continue
+    let buffer = [2; 256];
+    while self.1 != 0 {
+      let l = if self.1 > 256 { 256 } else { self.1 };
+      match r.write(&buffer[..l]) {
+        Ok(0) => return Err(Error::new(ErrorKind::WriteZero, "Could not write padding")),
+        Ok(l) => self.1 -= l,
+        Err(ref e) if e.kind() == ErrorKind::Interrupted => (),
+        Err(e) => return Err(e),
+      }
+    }
+    // terminal 0
+    try!(r.write_all(&[0]));
+    self.3 = true;
+    Ok(())
+  }
+}
+
+impl ExtRead for EndStream {
+  #[inline]
+  fn read_header<R : Read>(&mut self, _ : &mut R) -> Result<()> {
+    self.1 = self.0;
+    self.2 = false;
+    Ok(())}
+  #[inline]
+  fn read_from<R : Read>(&mut self, r : &mut R, buf : &mut[u8]) -> Result<usize> {
+
+    if buf.is_empty() {
+      return Ok(0)
+    }
+    try!(self.read_terminal(r));
+    if self.2 {
+      return Ok(0)
+    }
+    let l = if self.1 < buf.len() {
+      try!(r.read(&mut buf[..self.1]))
+    } else {
+      try!(r.read(buf))
+    };
+    if l == 0 {
+      return Err(Error::new(ErrorKind::UnexpectedEof, "End of stream in window"));
+    }
+
+    self.1 -= l;
+    Ok(l)
+  }
+
+  /// window content is dropped without copy, end of stream in a window is an error.
+  fn skip_from<R : Read>(&mut self, r : &mut R, n : usize) -> Result<usize> {
+    if n == 0 {
+      return Ok(0)
+    }
+    try!(self.read_terminal(r));
+    if self.2 {
+      return Ok(0)
+    }
+    let l = if self.1 < n { self.1 } else { n };
+    let mut t = r.by_ref().take(l as u64);
+    let res = io::copy(&mut t, &mut io::sink());
+    let rr = l - t.limit() as usize;
+    self.1 -= rr;
+    try!(res);
+    if rr == 0 {
+      return Err(Error::new(ErrorKind::UnexpectedEof, "End of stream in window"));
+    }
+    Ok(rr)
+  }
+
+  /// stay ended up to next header
+  #[inline]
+  fn read_end<R : Read>(&mut self, r : &mut R) -> Result<()> {
+    try!(self.discard_to_end(r));
+    Ok(())
+  }
+
+}
+
+/// similar to a reader/writer that encrypt content (symetric key in header, usage of internal
+/// buffer of fix size).
+/// It only shift byte.
+/// First u8 is the number of shift.
+/// Second its buffer and third write ix in buf
+/// The implementation only shift when buffer is full and add 0 bit padding to finalize (read and
+/// write buff must be of same size).
+/// Fourth is end char (default to 6)
+/// Last is progress of the buffer being written or read (kept on transport error)
+#[derive(Clone)]
+pub struct Ciph(u8, Vec<u8>, usize, u8, Option<usize>);
+
+impl Ciph {
+  pub fn new(shift : u8, bufsize : usize) -> Self {
+    Ciph(shift, vec![0;bufsize], 0, 6, None)
+  }
+  pub fn new_with_endval(shift : u8, bufsize : usize, endval : u8) -> Self {
+    Ciph(shift, vec![0;bufsize], 0, endval, None)
+  }
+
+  fn encode(&mut self) {
+    for i in &mut self.1 {
+      *i = shift_up(*i,self.0);
+    }
+    self.4 = Some(0);
+  }
+
+  /// forward enc buf if any
+  fn write_block<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    if let Some(mut ix) = self.4 {
+      while ix < self.1.len() {
+        match w.write(&self.1[ix..]) {
+          Ok(0) => return Err(Error::new(ErrorKind::WriteZero, "Could not write block")),
+          Ok(l) => {
+            ix += l;
+            self.4 = Some(ix);
+          },
+          Err(ref e) if e.kind() == ErrorKind::Interrupted => (),
+          Err(e) => return Err(e),
+        }
+      }
+      self.4 = None;
+      self.2 = 0;
+    }
+    Ok(())
+  }
+
+}
+
+impl ExtWrite for Ciph {
+
+  #[inline]
+  fn write_header<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    w.write_all(&[self.0])
+  }
+  #[inline]
+  fn write_into<W : Write>(&mut self, w : &mut W, cont : &[u8]) -> Result<usize> {
+    try!(self.write_block(w));
+    let mut tot = 0;
+    while tot < cont.len() {
+      let ibufsize = self.1.len() - self.2;
+      let contsize = cont.len() - tot;
+      // fill buf
+      let l = if ibufsize <  contsize {
+          //copy_memory(&cont[tot..tot + ibufsize], &mut self.1[self.2..]);
+          self.1[self.2..].clone_from_slice(&cont[tot..tot + ibufsize]);
+          ibufsize
+      } else {
+          //copy_memory(&cont[tot..], &mut self.1[self.2..]);
+          self.1[self.2..self.2 + contsize].clone_from_slice(&cont[tot..]);
+          contsize
+      };
+      tot += l;
+      self.2 += l;
+      if self.2 == self.1.len() {
+        // do encode buffer when full onl
+        self.encode();
+        if self.write_block(w).is_err() {
+          // content is in buffer, error will be returned on next call
+          return Ok(tot);
+        }
+      }
+    }
+    Ok(tot)
+  }
+
+  #[inline]
+  fn write_end<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    if self.2 != 0 && self.4.is_none() {
+      // write buffer (all buffer so end is padding)
+      self.encode();
+    }
+    try!(self.write_block(w));
+
+    // add data for test only
+    w.write_all(&[self.3])
+  }
+}
+
+impl ExtRead for Ciph {
+  #[inline]
+  fn read_header<R : Read>(&mut self, r : &mut R) -> Result<()> {
+    let buf = &mut [9];
+    try!(r.read_exact(buf));
+    self.0 = buf[0];
+    self.2 = self.1.len();
+    self.4 = None;
+    Ok(())
+  }
+  #[inline]
+  fn read_from<R : Read>(&mut self, r : &mut R, buf : &mut[u8]) -> Result<usize> {
+
+    if buf.is_empty() {
+      return Ok(0);
+    }
+    if self.2 == self.1.len() {
+      // need to read next block
+      let mut ix = self.4.unwrap_or(0);
+      while ix < self.1.len() {
+        let l = match r.read(&mut self.1[ix..]) {
+          Ok(l) => l,
+          Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+          Err(e) => return Err(e),
+        };
+        if l == 0 {
+          if ix == 0 {
+            // a multiple buf has been written but nothing could be read : we consider that it is
+            // no error (for instance with end stream)
+            return Ok(0);
+          } else {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "No bytes (encode should have written block buffer multiple content"));
+          }
+        }
+        ix += l;
+        self.4 = Some(ix);
+      }
+      for i in &mut self.1 {
+        *i = shift_down(*i,self.0);
+      }
+      self.4 = None;
+      self.2 = 0;
+    };
+    // read from buffer
+    let tocopy =  if buf.len() > self.1.len() - self.2 {
+        self.1.len() - self.2
+    } else {
+        buf.len()
+    };
+
+    //copy_memory(&self.1[self.2..self.2 + tocopy], &mut buf[..tocopy]);
+    buf[..tocopy].clone_from_slice(&self.1[self.2..self.2 + tocopy]);
+    self.2 += tocopy;
+    Ok(tocopy)
+  }
+  #[inline]
+  fn read_end<R : Read>(&mut self, r : &mut R) -> Result<()> {
+    let buf = &mut [9];
+    try!(r.read_exact(buf));
+    if buf[0] != self.3 {
+      return Err(Error::new(ErrorKind::InvalidData, "Wrong end value"));
+    }
+
+    // put ix at start val (the end of read content must have been padding)
+    self.2 = self.1.len();
+    Ok(())
+  }
+
+}
+
+
+#[inline]
+fn shift_up(init : u8, inc : u8) -> u8 {
+  (Wrapping(init) + Wrapping(inc)).0
+}
+#[inline]
+fn shift_down(init : u8, dec : u8) -> u8 {
+  (Wrapping(init) - Wrapping(dec)).0
+}
@@ -1,5 +1,8 @@
 extern crate readwrite_comp;
 extern crate rand;
+extern crate proptest;
+#[cfg(feature="async")]
+extern crate futures_io;
 use readwrite_comp::{
   ExtWrite,
   ExtRead,
@@ -10,23 +13,73 @@ use std::io::{
   Read,
   Cursor,
   Result,
-  //Error,
-  //ErrorKind,
+  Error,
+  ErrorKind,
 };
 
-use rand::Rng;
-use rand::os::OsRng;
+use rand::RngCore;
+use rand::rngs::OsRng;
 
+#[cfg(feature="async")]
+pub mod async_test;
+
+pub mod symmetry;
+pub mod schedule;
+pub mod transport;
+pub mod layers;
+
+pub use symmetry::{
+  check_symmetry,
+  SymmetryConfig,
+  SymmetryError,
+  SymmetryErrorKind,
+};
+
+pub use transport::check_transports;
+
+pub use schedule::{
+  Schedule,
+  MessageSchedule,
+  schedule_strategy,
+  run_schedule,
+  check_schedules,
+};
+
+
+/// run all checks over ext writer and reader built by `new_w` and `new_r` (a new instance for
+/// each check) : `test_bytes_wr` (short and long content), `check_symmetry`, `check_transports`
+/// and `check_schedules`.
+#[allow(clippy::io_other_error)]
+pub fn check_ext<BW : ExtWrite, BR : ExtRead, FW : Fn() -> BW, FR : Fn() -> BR>
+  (new_w : FW, new_r : FR, allow_padding : bool) -> Result<()> {
+  try!(test_bytes_wr(150, 7, &mut new_w(), &mut new_r()));
+  try!(test_bytes_wr(1000, 300, &mut new_w(), &mut new_r()));
+  let conf = SymmetryConfig {
+    allow_padding,
+    .. SymmetryConfig::default()
+  };
+  try!(check_symmetry(&conf, &mut new_w(), &mut new_r()));
+  try!(check_transports(&new_w, &new_r, allow_padding));
+  check_schedules(32, 4, 300, 40, &new_w, &new_r, allow_padding)
+    .map_err(|e|Error::new(ErrorKind::Other, format!("{}", e)))
+}
 
 pub fn test_bytes_wr<BW : ExtWrite, BR : ExtRead> 
   (inp_length : usize, buf_length : usize, bw : &mut BW, br : &mut BR) -> Result<()> {
-  let mut rng = OsRng::new().unwrap();
+  let mut bufb = vec![0;buf_length];
+  let (mut output, reference, endcontent) = try!(write_bytes(inp_length, &mut bufb, bw));
+  read_bytes(&mut output, &reference, endcontent, inp_length, &mut bufb, br)
+}
+
+/// write random content of `inp_length` with ext writer, followed by a random byte.
+/// Return written content, reference content and the byte written after end.
+fn write_bytes<BW : ExtWrite> 
+  (inp_length : usize, buf : &mut [u8], bw : &mut BW) -> Result<(Cursor<Vec<u8>>, Vec<u8>, u8)> {
+  let mut rng = OsRng;
   let mut outputb = Cursor::new(Vec::new());
   let mut reference = Cursor::new(Vec::new());
   let output = &mut outputb;
-  let mut bufb = vec![0;buf_length];
   let mut has_started = false;
-  let buf = &mut bufb;
   // knwoledge of size to write
   let mut i = 0;
   while inp_length > i {
@@ -52,13 +105,16 @@ pub fn test_bytes_wr<BW : ExtWrite, BR : ExtRead>
   println!("EndContent{}",endcontent);
   try!(output.write(&buf[..1]));
   output.flush().unwrap();
-
-  // no knowledge of size to read
   output.set_position(0);
-  let input = output;
-  has_started = false;
+  Ok((outputb, reference.into_inner(), endcontent))
+}
+
+/// read content written by `write_bytes` (no knowledge of size to read) and check it
+fn read_bytes<BR : ExtRead> 
+  (input : &mut Cursor<Vec<u8>>, reference : &[u8], endcontent : u8, inp_length : usize, buf : &mut [u8], br : &mut BR) -> Result<()> {
+  let mut has_started = false;
   let mut rr = 1;
-  i = 0;
+  let mut i = 0;
   while rr != 0 {
     if !has_started {
       try!(br.read_header(input));
@@ -72,10 +128,10 @@ pub fn test_bytes_wr<BW : ExtWrite, BR : ExtRead>
       if inp_length > i {
         let padstart = inp_length - i;
         println !("pad start {}",padstart);
-        assert!(buf[..padstart] == reference.get_ref()[i..]);
+        assert!(buf[..padstart] == reference[i..]);
       } // else the window is bigger than buffer and padding is being read
     } else {
-      assert!(buf[..rr] == reference.get_ref()[i..i + rr]);
+      assert!(buf[..rr] == reference[i..i + rr]);
     }
     }
     i += rr;
@@ -88,4 +144,3 @@ pub fn test_bytes_wr<BW : ExtWrite, BR : ExtRead>
   Ok(())
 
 }
-
@@ -0,0 +1,162 @@
+//! Property based testing of ExtWrite/ExtRead pairs : a `Schedule` describe messages content,
+//! write sizes, flush points, read sizes and transport limitations, `schedule_strategy` generate
+//! them (with shrinking on failure) through proptest.
+
+use readwrite_comp::{
+  ExtWrite,
+  ExtRead,
+};
+
+use std::io::{
+  Cursor,
+};
+
+use proptest::prelude::*;
+use proptest::test_runner::{
+  TestRunner,
+  TestError,
+  TestCaseError,
+  Config,
+};
+
+use symmetry::{
+  SymmetryError,
+  SymmetryErrorKind,
+  check_content,
+  read_raw,
+};
+use transport::{
+  ShortWrite,
+  ShortRead,
+};
+
+/// Message to write
+#[derive(Clone,Debug)]
+pub struct MessageSchedule {
+  pub content : Vec<u8>,
+  /// write sizes (cycling over it) with a flush after write if true
+  pub writes : Vec<(usize, bool)>,
+}
+
+/// Full stream description
+#[derive(Clone,Debug)]
+pub struct Schedule {
+  pub messages : Vec<MessageSchedule>,
+  /// read buffer sizes (cycling over it)
+  pub reads : Vec<usize>,
+  /// max bytes accepted by a transport write
+  pub transport_write : usize,
+  /// max bytes returned by a transport read
+  pub transport_read : usize,
+}
+
+/// strategy for schedules of at most `max_messages` messages of length at most `max_len`, with
+/// write, read and transport sizes at most `max_chunk`.
+pub fn schedule_strategy(max_messages : usize, max_len : usize, max_chunk : usize) -> BoxedStrategy<Schedule> {
+  let message = (
+    prop::collection::vec(any::<u8>(), 0..max_len + 1),
+    prop::collection::vec((1..max_chunk + 1, any::<bool>()), 1..8),
+  ).prop_map(|(content, writes)| MessageSchedule {
+    content,
+    writes,
+  });
+  (
+    prop::collection::vec(message, 0..max_messages + 1),
+    prop::collection::vec(1..max_chunk + 1, 1..8),
+    1..max_chunk + 1,
+    1..max_chunk + 1,
+  ).prop_map(|(messages, reads, transport_write, transport_read)| Schedule {
+    messages,
+    reads,
+    transport_write,
+    transport_read,
+  }).boxed()
+}
+
+/// write then read all messages of the schedule, return first divergence
+pub fn run_schedule<BW : ExtWrite, BR : ExtRead>
+  (s : &Schedule, bw : &mut BW, br : &mut BR, allow_padding : bool) -> ::std::result::Result<(), SymmetryError> {
+  let mut output = ShortWrite(Cursor::new(Vec::new()), s.transport_write);
+  for (i, m) in s.messages.iter().enumerate() {
+    try!(write_message(m, &mut output, bw).map_err(|e| SymmetryError {
+      seed : None,
+      message : i,
+      offset : 0,
+      stream_pos : (output.0).position(),
+      kind : SymmetryErrorKind::Write(e),
+    }));
+  }
+  // trailing byte
+  (output.0).get_mut().push(123);
+  let mut input = ShortRead(output.0, s.transport_read);
+  input.0.set_position(0);
+  let mut readix = 0;
+  for (i, m) in s.messages.iter().enumerate() {
+    try!(read_message(s, &mut readix, m, &mut input, br, allow_padding).map_err(|(offset, kind)| SymmetryError {
+      seed : None,
+      message : i,
+      offset,
+      stream_pos : input.0.position(),
+      kind,
+    }));
+  }
+  read_raw(&mut input, &[123]).map_err(|(offset, kind)| SymmetryError {
+    seed : None,
+    message : s.messages.len(),
+    offset,
+    stream_pos : input.0.position(),
+    kind,
+  })
+}
+
+fn write_message<BW : ExtWrite>
+  (m : &MessageSchedule, output : &mut ShortWrite<Cursor<Vec<u8>>>, bw : &mut BW) -> ::std::io::Result<()> {
+  try!(bw.write_header(output));
+  let mut i = 0;
+  let mut wix = 0;
+  while i < m.content.len() {
+    let (l, flush) = m.writes[wix % m.writes.len()];
+    wix += 1;
+    let end = if i + l > m.content.len() { m.content.len() } else { i + l };
+    try!(bw.write_all_into(output, &m.content[i..end]));
+    i = end;
+    if flush {
+      try!(bw.flush_into(output));
+    }
+  }
+  bw.write_end(output)
+}
+
+fn read_message<BR : ExtRead>
+  (s : &Schedule, readix : &mut usize, m : &MessageSchedule, input : &mut ShortRead<Cursor<Vec<u8>>>, br : &mut BR, allow_padding : bool)
+  -> ::std::result::Result<(), (usize, SymmetryErrorKind)> {
+  let mut buf = vec![0;s.reads.iter().max().cloned().unwrap_or(1)];
+  try!(br.read_header(input).map_err(|e|(0, SymmetryErrorKind::Read(e))));
+  let mut i = 0;
+  loop {
+    let l = s.reads[*readix % s.reads.len()];
+    *readix += 1;
+    let rr = try!(br.read_from(input, &mut buf[..l]).map_err(|e|(i, SymmetryErrorKind::Read(e))));
+    if rr == 0 {
+      break;
+    }
+    try!(check_content(allow_padding, &m.content[..], i, &buf[..rr]));
+    i += rr;
+  }
+  if i < m.content.len() {
+    return Err((i, SymmetryErrorKind::Missing));
+  }
+  br.read_end(input).map_err(|e|(i, SymmetryErrorKind::Read(e)))
+}
+
+/// run `cases` random schedules (see `schedule_strategy`) with new ext writer and reader for each
+/// schedule.
+pub fn check_schedules<BW : ExtWrite, BR : ExtRead, FW : Fn() -> BW, FR : Fn() -> BR>
+  (cases : u32, max_messages : usize, max_len : usize, max_chunk : usize, new_w : FW, new_r : FR, allow_padding : bool)
+  -> ::std::result::Result<(), TestError<Schedule>> {
+  let mut runner = TestRunner::new(Config::with_cases(cases));
+  runner.run(&schedule_strategy(max_messages, max_len, max_chunk), |s| {
+    run_schedule(&s, &mut new_w(), &mut new_r(), allow_padding)
+      .map_err(|e|TestCaseError::fail(format!("{}", e)))
+  })
+}
@@ -0,0 +1,312 @@
+//! Conformance checker for ExtWrite/ExtRead pairs : content written by the ExtWrite must be read
+//! back by the ExtRead, on a stream containing many messages.
+//!
+//! Each message is written with random write sizes and random flushes, either directly with
+//! the ext calls or through `CompW` (message ended by `suspend` and some raw bytes written in the
+//! transport before `resume`). Random trailing data is written after the last message.
+//! Raw bytes can be disabled for ExtRead reading ahead of the end of message (the checker reads
+//! raw bytes directly from the transport).
+//! Reading uses random buffer sizes and is done without knowledge of message length : the
+//! reader must return 0 at the end of a message.
+//!
+//! Random generation is seeded, the seed is reported in the error for reproduction.
+
+use readwrite_comp::{
+  ExtWrite,
+  ExtRead,
+  CompW,
+  CompR,
+};
+
+use std::io::{
+  Write,
+  Read,
+  Cursor,
+  Error,
+  ErrorKind,
+};
+use std::fmt;
+
+use rand::{
+  Rng,
+  RngCore,
+  SeedableRng,
+};
+use rand::rngs::{
+  OsRng,
+  StdRng,
+};
+
+/// Checker configuration
+#[derive(Clone,Debug)]
+pub struct SymmetryConfig {
+  /// number of messages in stream
+  pub nb_messages : usize,
+  /// max length of a message (random length between 0 and this value)
+  pub max_message_len : usize,
+  /// max size of a single write or read buffer (random between 1 and this value)
+  pub max_buf_len : usize,
+  /// probability (1 / n) of a flush after a write
+  pub flush_ratio : u32,
+  /// probability (1 / n) of using CompW with suspend/resume and raw bytes between messages
+  pub suspend_ratio : u32,
+  /// number of bytes written after last message
+  pub trailing_len : usize,
+  /// write raw bytes between messages and after last message (`trailing_len` is ignored if false)
+  pub raw_content : bool,
+  /// reader can return more content than written (padding)
+  pub allow_padding : bool,
+  /// seed for random generation (random seed if None)
+  pub seed : Option<[u32;4]>,
+}
+
+impl Default for SymmetryConfig {
+  fn default() -> Self {
+    SymmetryConfig {
+      nb_messages : 20,
+      max_message_len : 500,
+      max_buf_len : 64,
+      flush_ratio : 5,
+      suspend_ratio : 3,
+      trailing_len : 5,
+      raw_content : true,
+      allow_padding : false,
+      seed : None,
+    }
+  }
+}
+
+/// Kind of divergence between write and read
+#[derive(Debug)]
+pub enum SymmetryErrorKind {
+  /// io error on write (could not write content)
+  Write(Error),
+  /// io error on read
+  Read(Error),
+  /// read byte differs from written byte
+  Content { expected : u8, read : u8 },
+  /// reader ended message before the end of written content
+  Missing,
+  /// reader returned content after the end of written content (and padding is not allowed)
+  Padding,
+  /// raw content written between messages (after suspend) or after last message differs
+  Raw { expected : u8, read : Option<u8> },
+}
+
+/// First divergence found
+#[derive(Debug)]
+pub struct SymmetryError {
+  /// seed to reproduce the failure (None if not random)
+  pub seed : Option<[u32;4]>,
+  /// index of message
+  pub message : usize,
+  /// offset of first diverging byte in message content (or in raw content)
+  pub offset : usize,
+  /// position in transport when the divergence was found
+  pub stream_pos : u64,
+  pub kind : SymmetryErrorKind,
+}
+
+impl fmt::Display for SymmetryError {
+  fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+    try!(write!(f, "symmetry failure on message {} at offset {} (stream position {}",
+      self.message, self.offset, self.stream_pos));
+    if let Some(seed) = self.seed {
+      try!(write!(f, ", seed {:?}", seed));
+    }
+    write!(f, ") : {:?}", self.kind)
+  }
+}
+
+impl From<SymmetryError> for Error {
+  #[allow(clippy::io_other_error)]
+  fn from(e : SymmetryError) -> Error {
+    Error::new(ErrorKind::Other, format!("{}", e))
+  }
+}
+
+/// Message content and number of raw bytes written after it (in transport)
+struct WrittenMessage {
+  content : Vec<u8>,
+  raw : Vec<u8>,
+  comp : bool,
+}
+
+/// check that `br` read what `bw` write, return the first divergence.
+pub fn check_symmetry<BW : ExtWrite, BR : ExtRead>
+  (conf : &SymmetryConfig, bw : &mut BW, br : &mut BR) -> ::std::result::Result<(), SymmetryError> {
+  let seed = match conf.seed {
+    Some(s) => s,
+    None => {
+      let mut osrng = OsRng;
+      [osrng.gen(), osrng.gen(), osrng.gen(), osrng.gen()]
+    },
+  };
+  let mut rng = seeded_rng(seed);
+  let mut output = Cursor::new(Vec::new());
+  let mut messages = Vec::with_capacity(conf.nb_messages);
+  for i in 0..conf.nb_messages {
+    let m = try!(write_message(conf, &mut rng, &mut output, bw).map_err(|e|SymmetryError {
+      seed : Some(seed),
+      message : i,
+      offset : 0,
+      stream_pos : output.position(),
+      kind : SymmetryErrorKind::Write(e),
+    }));
+    messages.push(m);
+  }
+  let mut trailing = vec![0;if conf.raw_content { conf.trailing_len } else { 0 }];
+  rng.fill_bytes(&mut trailing[..]);
+  output.write_all(&trailing[..]).unwrap();
+
+  let mut input = output;
+  input.set_position(0);
+  for (i, m) in messages.iter().enumerate() {
+    try!(read_message(conf, &mut rng, &mut input, br, m).map_err(|(offset, kind)| SymmetryError {
+      seed : Some(seed),
+      message : i,
+      offset,
+      stream_pos : input.position(),
+      kind,
+    }));
+  }
+  try!(read_raw(&mut input, &trailing[..]).map_err(|(offset, kind)| SymmetryError {
+    seed : Some(seed),
+    message : conf.nb_messages,
+    offset,
+    stream_pos : input.position(),
+    kind,
+  }));
+  Ok(())
+}
+
+/// deterministic rng from a checker seed
+fn seeded_rng(seed : [u32;4]) -> StdRng {
+  let mut s = [0;32];
+  for (i, v) in seed.iter().enumerate() {
+    s[i * 4..(i + 1) * 4].copy_from_slice(&v.to_le_bytes());
+  }
+  StdRng::from_seed(s)
+}
+
+/// true with probability 1 / n (always if n is 0 or 1)
+fn one_in<RNG : Rng>(rng : &mut RNG, n : u32) -> bool {
+  n <= 1 || rng.gen_ratio(1, n)
+}
+
+fn write_message<BW : ExtWrite, RNG : Rng>
+  (conf : &SymmetryConfig, rng : &mut RNG, output : &mut Cursor<Vec<u8>>, bw : &mut BW) -> ::std::io::Result<WrittenMessage> {
+  let len = rng.gen_range(0..conf.max_message_len + 1);
+  let mut content = vec![0;len];
+  rng.fill_bytes(&mut content[..]);
+  let comp = one_in(rng, conf.suspend_ratio);
+  let mut raw = Vec::new();
+  if comp {
+    {
+      let mut compw = CompW::new(output, bw);
+      // empty write for header
+      try!(compw.write(&[]));
+      let mut i = 0;
+      while i < len {
+        let l = rng.gen_range(1..conf.max_buf_len + 1);
+        let end = if i + l > len { len } else { i + l };
+        try!(compw.write_all(&content[i..end]));
+        i = end;
+        if one_in(rng, conf.flush_ratio) {
+          try!(compw.flush());
+        }
+      }
+      try!(compw.suspend());
+    }
+    raw = vec![0;if conf.raw_content { rng.gen_range(0..3) } else { 0 }];
+    rng.fill_bytes(&mut raw[..]);
+    try!(output.write_all(&raw[..]));
+  } else {
+    try!(bw.write_header(output));
+    let mut i = 0;
+    while i < len {
+      let l = rng.gen_range(1..conf.max_buf_len + 1);
+      let end = if i + l > len { len } else { i + l };
+      try!(bw.write_all_into(output, &content[i..end]));
+      i = end;
+      if one_in(rng, conf.flush_ratio) {
+        try!(bw.flush_into(output));
+      }
+    }
+    try!(bw.write_end(output));
+  }
+  Ok(WrittenMessage {
+    content,
+    raw,
+    comp,
+  })
+}
+
+fn read_message<BR : ExtRead, RNG : Rng>
+  (conf : &SymmetryConfig, rng : &mut RNG, input : &mut Cursor<Vec<u8>>, br : &mut BR, m : &WrittenMessage)
+  -> ::std::result::Result<(), (usize, SymmetryErrorKind)> {
+  let mut buf = vec![0;conf.max_buf_len];
+  let mut i = 0;
+  if m.comp {
+    {
+      let mut compr = CompR::new(input, br);
+      loop {
+        let l = rng.gen_range(1..conf.max_buf_len + 1);
+        let rr = try!(compr.read(&mut buf[..l]).map_err(|e|(i, SymmetryErrorKind::Read(e))));
+        if rr == 0 {
+          break;
+        }
+        try!(check_content(conf.allow_padding, &m.content[..], i, &buf[..rr]));
+        i += rr;
+      }
+      try!(compr.suspend().map_err(|e|(i, SymmetryErrorKind::Read(e))));
+    }
+    if i < m.content.len() {
+      return Err((i, SymmetryErrorKind::Missing));
+    }
+    read_raw(input, &m.raw[..])
+  } else {
+    try!(br.read_header(input).map_err(|e|(0, SymmetryErrorKind::Read(e))));
+    loop {
+      let l = rng.gen_range(1..conf.max_buf_len + 1);
+      let rr = try!(br.read_from(input, &mut buf[..l]).map_err(|e|(i, SymmetryErrorKind::Read(e))));
+      if rr == 0 {
+        break;
+      }
+      try!(check_content(conf.allow_padding, &m.content[..], i, &buf[..rr]));
+      i += rr;
+    }
+    if i < m.content.len() {
+      return Err((i, SymmetryErrorKind::Missing));
+    }
+    br.read_end(input).map_err(|e|(i, SymmetryErrorKind::Read(e)))
+  }
+}
+
+pub(crate) fn check_content(allow_padding : bool, content : &[u8], offset : usize, read : &[u8])
+  -> ::std::result::Result<(), (usize, SymmetryErrorKind)> {
+  for (j, b) in read.iter().enumerate() {
+    let o = offset + j;
+    if o >= content.len() {
+      if allow_padding {
+        return Ok(());
+      }
+      return Err((o, SymmetryErrorKind::Padding));
+    }
+    if content[o] != *b {
+      return Err((o, SymmetryErrorKind::Content { expected : content[o], read : *b }));
+    }
+  }
+  Ok(())
+}
+
+pub(crate) fn read_raw<R : Read>(input : &mut R, raw : &[u8]) -> ::std::result::Result<(), (usize, SymmetryErrorKind)> {
+  let mut b = [0];
+  for (i, e) in raw.iter().enumerate() {
+    let rr = try!(input.read(&mut b).map_err(|e|(i, SymmetryErrorKind::Read(e))));
+    if rr == 0 || b[0] != *e {
+      return Err((i, SymmetryErrorKind::Raw { expected : *e, read : if rr == 0 { None } else { Some(b[0]) } }));
+    }
+  }
+  Ok(())
+}
@@ -0,0 +1,276 @@
+//! In memory transports with constrained behavior, to check that ext implementations does not
+//! assume a single `write` or `read` call transfer everything, and manage io errors correctly.
+//!
+//! `check_transports` run an ExtWrite/ExtRead pair over all of them.
+
+use readwrite_comp::{
+  ExtWrite,
+  ExtRead,
+};
+
+use std::io::{
+  Write,
+  Read,
+  Cursor,
+  Result,
+  Error,
+  ErrorKind,
+  IoSlice,
+};
+
+use symmetry::check_content;
+
+/// Writer accepting at most `N` bytes per write call.
+pub struct ShortWrite<W : Write>(pub W, pub usize);
+
+impl<W : Write> Write for ShortWrite<W> {
+  fn write(&mut self, buf : &[u8]) -> Result<usize> {
+    let l = if buf.len() > self.1 { self.1 } else { buf.len() };
+    self.0.write(&buf[..l])
+  }
+  fn flush(&mut self) -> Result<()> {
+    self.0.flush()
+  }
+}
+
+/// Writer recording the length of each write call (vectored writes are recorded as a single call).
+pub struct CallWrite<W : Write>(pub W, pub Vec<usize>);
+
+impl<W : Write> Write for CallWrite<W> {
+  fn write(&mut self, buf : &[u8]) -> Result<usize> {
+    let l = try!(self.0.write(buf));
+    self.1.push(l);
+    Ok(l)
+  }
+  fn write_vectored(&mut self, bufs : &[IoSlice]) -> Result<usize> {
+    let l = try!(self.0.write_vectored(bufs));
+    self.1.push(l);
+    Ok(l)
+  }
+  fn flush(&mut self) -> Result<()> {
+    self.0.flush()
+  }
+}
+
+/// Reader returning at most `N` bytes per read call.
+pub struct ShortRead<R : Read>(pub R, pub usize);
+
+impl<R : Read> Read for ShortRead<R> {
+  fn read(&mut self, buf : &mut [u8]) -> Result<usize> {
+    let l = if buf.len() > self.1 { self.1 } else { buf.len() };
+    self.0.read(&mut buf[..l])
+  }
+}
+
+/// Writer returning an error of a given kind (for instance `Interrupted` or `WouldBlock`) every
+/// `period` calls (write and flush), nothing is written on error.
+pub struct FaultWrite<W : Write> {
+  pub inner : W,
+  kind : ErrorKind,
+  period : usize,
+  count : usize,
+}
+
+impl<W : Write> FaultWrite<W> {
+  pub fn new(inner : W, kind : ErrorKind, period : usize) -> Self {
+    FaultWrite {
+      inner,
+      kind,
+      period,
+      count : 0,
+    }
+  }
+}
+
+#[inline]
+#[allow(clippy::manual_is_multiple_of)]
+fn fault(count : &mut usize, period : usize, kind : ErrorKind) -> Result<()> {
+  *count += 1;
+  if *count % period == 0 {
+    Err(Error::new(kind, "injected transport error"))
+  } else {
+    Ok(())
+  }
+}
+
+impl<W : Write> Write for FaultWrite<W> {
+  fn write(&mut self, buf : &[u8]) -> Result<usize> {
+    try!(fault(&mut self.count, self.period, self.kind));
+    self.inner.write(buf)
+  }
+  fn flush(&mut self) -> Result<()> {
+    try!(fault(&mut self.count, self.period, self.kind));
+    self.inner.flush()
+  }
+}
+
+/// Reader returning an error of a given kind every `period` calls, nothing is read on error.
+pub struct FaultRead<R : Read> {
+  pub inner : R,
+  kind : ErrorKind,
+  period : usize,
+  count : usize,
+}
+
+impl<R : Read> FaultRead<R> {
+  pub fn new(inner : R, kind : ErrorKind, period : usize) -> Self {
+    FaultRead {
+      inner,
+      kind,
+      period,
+      count : 0,
+    }
+  }
+}
+
+impl<R : Read> Read for FaultRead<R> {
+  fn read(&mut self, buf : &mut [u8]) -> Result<usize> {
+    try!(fault(&mut self.count, self.period, self.kind));
+    self.inner.read(buf)
+  }
+}
+
+/// Reader reaching end of stream after `N` bytes (for instance in the middle of a frame).
+pub struct EofRead<R : Read>(pub R, pub usize);
+
+impl<R : Read> Read for EofRead<R> {
+  fn read(&mut self, buf : &mut [u8]) -> Result<usize> {
+    let l = if buf.len() > self.1 { self.1 } else { buf.len() };
+    let r = try!(self.0.read(&mut buf[..l]));
+    self.1 -= r;
+    Ok(r)
+  }
+}
+
+/// message lengths used by `check_transports`
+const MESSAGES : [usize;4] = [0, 1, 300, 17];
+
+/// max number of read call for a message (a reader not progressing is considered looping)
+const MAX_READS : usize = 10_000;
+
+fn message(ix : usize, len : usize) -> Vec<u8> {
+  (0..len).map(|i|(i * 7 + ix) as u8).collect()
+}
+
+#[inline]
+fn retry(e : &Error, kinds : &[ErrorKind]) -> bool {
+  kinds.iter().any(|k| *k == e.kind())
+}
+
+/// call `f` again while it fails with `WouldBlock` (if in `retry_kinds`), for header and end calls
+/// (other kinds must be managed by the ext implementation).
+pub fn retry_blocking<T, F : FnMut() -> Result<T>>(retry_kinds : &[ErrorKind], mut f : F) -> Result<T> {
+  loop {
+    match f() {
+      Err(ref e) if e.kind() == ErrorKind::WouldBlock && retry(e, retry_kinds) => (),
+      r => return r,
+    }
+  }
+}
+
+/// write all messages, retrying `write_into` on errors of kind `retry_kinds`
+fn write_messages<W : Write, BW : ExtWrite>(w : &mut W, bw : &mut BW, retry_kinds : &[ErrorKind]) -> Result<()> {
+  for (ix, len) in MESSAGES.iter().enumerate() {
+    let m = message(ix, *len);
+    try!(retry_blocking(retry_kinds, || bw.write_header(w)));
+    let mut i = 0;
+    while i < m.len() {
+      let end = if i + 13 > m.len() { m.len() } else { i + 13 };
+      match bw.write_into(w, &m[i..end]) {
+        Ok(0) => return Err(Error::new(ErrorKind::WriteZero, "ext write zero")),
+        Ok(n) => i += n,
+        Err(ref e) if retry(e, retry_kinds) => (),
+        Err(e) => return Err(e),
+      }
+    }
+    try!(retry_blocking(retry_kinds, || bw.write_end(w)));
+  }
+  Ok(())
+}
+
+/// read all messages, retrying `read_from` on errors of kind `retry_kinds`
+#[allow(clippy::io_other_error)]
+fn read_messages<R : Read, BR : ExtRead>(r : &mut R, br : &mut BR, retry_kinds : &[ErrorKind], allow_padding : bool) -> Result<()> {
+  let mut buf = [0;11];
+  for (ix, len) in MESSAGES.iter().enumerate() {
+    let m = message(ix, *len);
+    try!(retry_blocking(retry_kinds, || br.read_header(r)));
+    let mut i = 0;
+    let mut nb_read = 0;
+    loop {
+      nb_read += 1;
+      if nb_read > MAX_READS {
+        return Err(Error::new(ErrorKind::Other, format!("reader loops on message {}", ix)));
+      }
+      match br.read_from(r, &mut buf) {
+        Ok(0) => break,
+        Ok(n) => {
+          try!(check_content(allow_padding, &m[..], i, &buf[..n]).map_err(|(o, k)|
+            Error::new(ErrorKind::Other, format!("message {} diverge at {} : {:?}", ix, o, k))));
+          i += n;
+        },
+        Err(ref e) if retry(e, retry_kinds) => (),
+        Err(e) => return Err(e),
+      }
+    }
+    if i < m.len() {
+      return Err(Error::new(ErrorKind::Other, format!("message {} missing content at {}", ix, i)));
+    }
+    try!(retry_blocking(retry_kinds, || br.read_end(r)));
+  }
+  Ok(())
+}
+
+/// run ext writer and reader (new instances for each case) over :
+/// - short write (1 and 3 bytes per call) and short read (1 byte per call).
+/// - transports returning `Interrupted` (must be transparent for the pair, `write_into` and
+///   `read_from` are retried).
+/// - transports returning `WouldBlock` (all calls are retried, on error they must keep their
+///   progress so that messages are read back unchanged).
+/// - end of stream at every position of a message (read must fail).
+#[allow(clippy::io_other_error)]
+pub fn check_transports<BW : ExtWrite, BR : ExtRead, FW : Fn() -> BW, FR : Fn() -> BR>
+  (new_w : FW, new_r : FR, allow_padding : bool) -> Result<()> {
+  let ctx = |c : &'static str| move |e : Error| Error::new(e.kind(), format!("{} : {}", c, e));
+
+  // short write, short read
+  for n in [1, 3].iter() {
+    let mut w = ShortWrite(Cursor::new(Vec::new()), *n);
+    try!(write_messages(&mut w, &mut new_w(), &[]).map_err(ctx("short write")));
+    let mut c = w.0;
+    c.set_position(0);
+    try!(read_messages(&mut ShortRead(c, 1), &mut new_r(), &[], allow_padding).map_err(ctx("short read")));
+  }
+
+  // interrupted
+  for period in [2, 3].iter() {
+    let mut w = FaultWrite::new(Cursor::new(Vec::new()), ErrorKind::Interrupted, *period);
+    try!(write_messages(&mut w, &mut new_w(), &[ErrorKind::Interrupted]).map_err(ctx("interrupted write")));
+    let mut c = w.inner;
+    c.set_position(0);
+    let mut r = FaultRead::new(c, ErrorKind::Interrupted, *period);
+    try!(read_messages(&mut r, &mut new_r(), &[ErrorKind::Interrupted], allow_padding).map_err(ctx("interrupted read")));
+  }
+
+  // would block
+  for period in [2, 3].iter() {
+    let mut w = FaultWrite::new(ShortWrite(Cursor::new(Vec::new()), 5), ErrorKind::WouldBlock, *period);
+    try!(write_messages(&mut w, &mut new_w(), &[ErrorKind::WouldBlock]).map_err(ctx("would block write")));
+    let mut c = (w.inner).0;
+    c.set_position(0);
+    let mut r = FaultRead::new(ShortRead(c, 5), ErrorKind::WouldBlock, *period);
+    try!(read_messages(&mut r, &mut new_r(), &[ErrorKind::WouldBlock], allow_padding).map_err(ctx("would block read")));
+  }
+
+  // eof in the middle of a frame
+  let mut w = Cursor::new(Vec::new());
+  try!(write_messages(&mut w, &mut new_w(), &[]));
+  let c = w.into_inner();
+  for trunc in 0..c.len() {
+    let mut r = EofRead(Cursor::new(&c[..]), trunc);
+    if read_messages(&mut r, &mut new_r(), &[], allow_padding).is_ok() {
+      return Err(Error::new(ErrorKind::Other, format!("truncated stream at {} not detected", trunc)));
+    }
+  }
+  Ok(())
+}
@@ -0,0 +1,531 @@
+//! Async counterparts of ExtWrite and ExtRead, over futures-io AsyncWrite and AsyncRead.
+//!
+//! Semantic is the same as for the sync traits (same wire format is expected from an
+//! implementation of both traits), with an additional constraint : when `Poll::Pending` is
+//! returned the call will be repeated later (with the same content for write), so an
+//! implementation must keep in its state every byte already written or read (see `PollBuf` for
+//! control bytes).
+//! Mixing sync and async calls on the same ext instance in the middle of a message is not
+//! supported.
+//!
+//! There is no drop finalization for async composition (no way to poll in drop) : `poll_write_end`
+//! and `poll_read_end` need to be called manually (or through `poll_close` for `AsyncCompW`).
+//!
+//! Only part of the sync composition is available :
+//! - `AsyncCompW`/`AsyncCompR` (no owned variants), `AsyncCompR` has no auto read end mode and no
+//!   drop policy (as there is no drop finalization), message end must be read with
+//!   `poll_read_end`.
+//! - layers are composed with `MultiWExt`/`MultiRExt` (`AsyncMCompW`/`AsyncMCompR`), there is no
+//!   async `CompExtW`/`CompExtR` (nor `Duplex` or chaining), so composed layers must be of the same
+//!   type.
+//! - no async `MessageWriter`/`MessageReader`.
+
+use std::io::{
+  Result,
+  Error,
+  ErrorKind,
+};
+use std::pin::Pin;
+use std::task::{
+  Context,
+  Poll,
+};
+use futures_io::{
+  AsyncRead,
+  AsyncWrite,
+};
+use super::{
+  CompWState,
+  CompRState,
+  MultiWExt,
+  MultiRExt,
+  ID,
+};
+
+/// return on pending or error, similar to `try!` for `Poll<Result<T>>`
+macro_rules! try_ready {($e:expr) => (
+  match $e {
+    ::std::task::Poll::Ready(Ok(t)) => t,
+    ::std::task::Poll::Ready(Err(e)) => return ::std::task::Poll::Ready(Err(From::from(e))),
+    ::std::task::Poll::Pending => return ::std::task::Poll::Pending,
+  }
+)}
+
+/// AsyncWrite with further common functionnalities (see ExtWrite).
+pub trait AsyncExtWrite {
+
+  /// write header if needed
+  fn poll_write_header<W : AsyncWrite + Unpin>(&mut self, &mut Context, &mut W) -> Poll<Result<()>>;
+
+  /// write buffer.
+  fn poll_write_into<W : AsyncWrite + Unpin>(&mut self, &mut Context, &mut W, &[u8]) -> Poll<Result<usize>>;
+
+  /// Could add end content (padding...) only if read can manage it
+  /// does not flush recursivly
+  #[inline]
+  fn poll_flush_into<W : AsyncWrite + Unpin>(&mut self, _ : &mut Context, _ : &mut W) -> Poll<Result<()>> {
+    Poll::Ready(Ok(()))
+  }
+
+  /// write content at the end of stream. Read will be able to read it with a call to
+  /// poll_read_end.
+  fn poll_write_end<W : AsyncWrite + Unpin>(&mut self, &mut Context, &mut W) -> Poll<Result<()>>;
+
+}
+
+/// AsyncRead with additional possibility to read an end content (see ExtRead).
+pub trait AsyncExtRead {
+
+  /// read header (to initiate internal state) if needed
+  fn poll_read_header<R : AsyncRead + Unpin>(&mut self, &mut Context, &mut R) -> Poll<Result<()>>;
+
+  /// read in buffer.
+  fn poll_read_from<R : AsyncRead + Unpin>(&mut self, &mut Context, &mut R, &mut[u8]) -> Poll<Result<usize>>;
+
+  /// read end bytes (and possibly update internal state).
+  fn poll_read_end<R : AsyncRead + Unpin>(&mut self, &mut Context, &mut R) -> Poll<Result<()>>;
+
+}
+
+/// Resumable bytes buffer : to write or read a few control bytes (header, window size...) over
+/// multiple polls.
+/// Used either for writing (`push` then `poll_drain`) or for reading (`poll_fill` then `content`
+/// and `clear`), not both at the same time.
+#[derive(Clone,Default)]
+pub struct PollBuf {
+  buf : Vec<u8>,
+  pos : usize,
+}
+
+impl PollBuf {
+  #[inline]
+  pub fn new() -> Self {
+    PollBuf {
+      buf : Vec::new(),
+      pos : 0,
+    }
+  }
+
+  /// no bytes pending for write
+  #[inline]
+  pub fn is_empty(&self) -> bool {
+    self.pos == self.buf.len()
+  }
+
+  #[inline]
+  pub fn clear(&mut self) {
+    self.buf.clear();
+    self.pos = 0;
+  }
+
+  /// add bytes to write
+  #[inline]
+  pub fn push(&mut self, cont : &[u8]) {
+    if self.is_empty() {
+      self.clear();
+    }
+    self.buf.extend_from_slice(cont);
+  }
+
+  /// number of pending bytes already written
+  #[inline]
+  pub fn written(&self) -> usize {
+    self.pos
+  }
+
+  /// bytes read by `poll_fill`
+  #[inline]
+  pub fn content(&self) -> &[u8] {
+    &self.buf[..]
+  }
+
+  /// write all pending bytes
+  pub fn poll_drain<W : AsyncWrite + Unpin>(&mut self, cx : &mut Context, w : &mut W) -> Poll<Result<()>> {
+    while self.pos < self.buf.len() {
+      let nb = try_ready!(Pin::new(&mut *w).poll_write(cx, &self.buf[self.pos..]));
+      if nb == 0 {
+        return Poll::Ready(Err(Error::new(ErrorKind::WriteZero, "failed to write whole buffer")));
+      }
+      self.pos += nb;
+    }
+    self.clear();
+    Poll::Ready(Ok(()))
+  }
+
+  /// read until `nb` bytes are buffered
+  pub fn poll_fill<R : AsyncRead + Unpin>(&mut self, cx : &mut Context, r : &mut R, nb : usize) -> Poll<Result<()>> {
+    while self.buf.len() < nb {
+      let start = self.buf.len();
+      self.buf.resize(nb, 0);
+      match Pin::new(&mut *r).poll_read(cx, &mut self.buf[start..]) {
+        Poll::Ready(Ok(0)) => {
+          self.buf.truncate(start);
+          return Poll::Ready(Err(Error::new(ErrorKind::UnexpectedEof, "failed to fill whole buffer")));
+        },
+        Poll::Ready(Ok(rr)) => self.buf.truncate(start + rr),
+        Poll::Ready(Err(e)) => {
+          self.buf.truncate(start);
+          return Poll::Ready(Err(e));
+        },
+        Poll::Pending => {
+          self.buf.truncate(start);
+          return Poll::Pending;
+        },
+      }
+    }
+    Poll::Ready(Ok(()))
+  }
+}
+
+/// Async version of CompW.
+/// There is no drop finalization : `poll_close` (or `poll_write_end`) must be polled to end the
+/// message.
+pub struct AsyncCompW<'a, 'b, W : 'a + AsyncWrite + Unpin, EW : 'b + AsyncExtWrite>(pub &'a mut W, pub &'b mut EW, pub CompWState);
+
+/// Async version of CompR.
+pub struct AsyncCompR<'a, 'b, R : 'a + AsyncRead + Unpin, ER : 'b + AsyncExtRead>(pub &'a mut R, pub &'b mut ER, pub CompRState);
+
+impl<'a, 'b, W : 'a + AsyncWrite + Unpin, EW : 'b + AsyncExtWrite> AsyncCompW<'a,'b,W,EW> {
+
+  #[inline]
+  pub fn new(w : &'a mut W, ew : &'b mut EW) -> Self {
+    AsyncCompW(w,ew,CompWState::Initial)
+  }
+
+  #[inline]
+  /// suspend write (inner writer is available again), `poll_write_end` should be call before to
+  /// end current message.
+  pub fn suspend(self) -> (&'b mut EW, CompWState) {
+    (self.1,self.2)
+  }
+
+  #[inline]
+  pub fn resume(with : &'a mut W, from : (&'b mut EW, CompWState)) -> Self {
+    AsyncCompW(with, from.0, from.1)
+  }
+
+  #[inline]
+  pub fn poll_write_end(&mut self, cx : &mut Context) -> Poll<Result<()>> {
+    if let CompWState::HeadWritten = self.2 {
+      try_ready!(self.1.poll_write_end(cx, self.0));
+      self.2 = CompWState::Initial;
+    }
+    Poll::Ready(Ok(()))
+  }
+
+}
+
+impl<'a, 'b, R : 'a + AsyncRead + Unpin, ER : 'b + AsyncExtRead> AsyncCompR<'a,'b,R,ER> {
+
+  #[inline]
+  pub fn new(r : &'a mut R, er : &'b mut ER) -> Self {
+    AsyncCompR(r,er,CompRState::Initial)
+  }
+
+  #[inline]
+  /// suspend read, `poll_read_end` should be call before to end current message.
+  pub fn suspend(self) -> (&'b mut ER, CompRState) {
+    (self.1,self.2)
+  }
+
+  #[inline]
+  pub fn resume(with : &'a mut R, from : (&'b mut ER, CompRState)) -> Self {
+    AsyncCompR(with, from.0, from.1)
+  }
+
+  #[inline]
+  pub fn poll_read_end(&mut self, cx : &mut Context) -> Poll<Result<()>> {
+    if let CompRState::HeadRead = self.2 {
+      try_ready!(self.1.poll_read_end(cx, self.0));
+      self.2 = CompRState::Initial;
+    }
+    Poll::Ready(Ok(()))
+  }
+
+}
+
+impl<'a, 'b, W : 'a + AsyncWrite + Unpin, EW : 'b + AsyncExtWrite> AsyncWrite for AsyncCompW<'a,'b,W,EW> {
+  fn poll_write(self : Pin<&mut Self>, cx : &mut Context, cont : &[u8]) -> Poll<Result<usize>> {
+    let this = self.get_mut();
+    if let CompWState::Initial = this.2 {
+      try_ready!(this.1.poll_write_header(cx, this.0));
+      this.2 = CompWState::HeadWritten;
+    }
+    this.1.poll_write_into(cx, this.0, cont)
+  }
+  fn poll_flush(self : Pin<&mut Self>, cx : &mut Context) -> Poll<Result<()>> {
+    let this = self.get_mut();
+    try_ready!(this.1.poll_flush_into(cx, this.0));
+    Pin::new(&mut *this.0).poll_flush(cx)
+  }
+  /// write end and flush, then close inner writer
+  fn poll_close(self : Pin<&mut Self>, cx : &mut Context) -> Poll<Result<()>> {
+    let this = self.get_mut();
+    try_ready!(this.poll_write_end(cx));
+    try_ready!(this.1.poll_flush_into(cx, this.0));
+    Pin::new(&mut *this.0).poll_close(cx)
+  }
+}
+
+impl<'a, 'b, R : 'a + AsyncRead + Unpin, ER : 'b + AsyncExtRead> AsyncRead for AsyncCompR<'a,'b,R,ER> {
+  fn poll_read(self : Pin<&mut Self>, cx : &mut Context, buf : &mut [u8]) -> Poll<Result<usize>> {
+    let this = self.get_mut();
+    if let CompRState::Initial = this.2 {
+      try_ready!(this.1.poll_read_header(cx, this.0));
+      this.2 = CompRState::HeadRead;
+    }
+    this.1.poll_read_from(cx, this.0, buf)
+  }
+}
+
+/// Async MultiW (see MultiW).
+pub type AsyncMultiW<'a, 'b, W, EW> = AsyncCompW<'a,'b,W,MultiWExt<EW>>;
+
+/// Async MultiR (see MultiR).
+pub type AsyncMultiR<'a, 'b, R, ER> = AsyncCompR<'a,'b,R,MultiRExt<ER>>;
+
+#[inline]
+pub fn new_async_multiw<'a, 'b, W : 'a + AsyncWrite + Unpin, EW : 'b + AsyncExtWrite>
+  (w : &'a mut W, ew : &'b mut MultiWExt<EW>) -> AsyncMultiW<'a,'b,W,EW> {
+    AsyncCompW::new(w, ew)
+}
+
+#[inline]
+pub fn new_async_multir<'a, 'b, R : 'a + AsyncRead + Unpin, ER : 'b + AsyncExtRead>
+  (r : &'a mut R, er : &'b mut MultiRExt<ER>) -> AsyncMultiR<'a,'b,R,ER> {
+    AsyncCompR::new(r, er)
+}
+
+/// async variant of MCompW
+struct AsyncMCompW<'a, 'b, W : 'a + AsyncWrite + Unpin, EW : 'b + AsyncExtWrite>(&'a mut W, &'b mut[EW], &'b mut [CompWState]);
+
+/// async variant of MCompR
+struct AsyncMCompR<'a, 'b, R : 'a + AsyncRead + Unpin, ER : 'b + AsyncExtRead>(&'a mut R, &'b mut[ER], &'b mut [CompRState]);
+
+impl<'a, 'b, W : 'a + AsyncWrite + Unpin, EW : 'b + AsyncExtWrite> AsyncMCompW<'a,'b,W,EW> {
+
+  /// Unlike sync version, next layers header are written before the layer header (same
+  /// result as sync version where first write of layer header write next layers header), so
+  /// that a pending next layer does not lead to write the layer header twice.
+  fn poll_write_header(&mut self, cx : &mut Context) -> Poll<Result<()>> {
+    if self.1.is_empty() {
+      return Poll::Ready(Ok(()));
+    }
+    if let CompWState::Initial = self.2[0] {
+      if self.1.len() > 1 {
+        if let Some((f,last)) = self.1.split_first_mut() {
+          let mut el = AsyncMCompW(&mut *self.0, last, &mut self.2[1..]);
+          try_ready!(el.poll_write_header(cx));
+          try_ready!(f.poll_write_header(cx, &mut el));
+        }
+      } else {
+        try_ready!(self.1[0].poll_write_header(cx, self.0));
+      }
+      self.2[0] = CompWState::HeadWritten;
+    }
+    Poll::Ready(Ok(()))
+  }
+
+  /// layer state is switched before next layers end, so that a pending next layer does not lead
+  /// to write the layer end twice (next layers end is no op when already ended).
+  fn poll_write_end(&mut self, cx : &mut Context) -> Poll<Result<()>> {
+    if let (Some((f,last)), Some((state,last_state))) = (self.1.split_first_mut(), self.2.split_first_mut()) {
+      if !last.is_empty() {
+        let mut el = AsyncMCompW(&mut *self.0, last, last_state);
+        if let CompWState::HeadWritten = *state {
+          try_ready!(f.poll_write_end(cx, &mut el));
+          *state = CompWState::Initial;
+        }
+        try_ready!(el.poll_write_end(cx));
+      } else if let CompWState::HeadWritten = *state {
+        // last
+        try_ready!(f.poll_write_end(cx, self.0));
+        *state = CompWState::Initial;
+      }
+    }
+    Poll::Ready(Ok(()))
+  }
+
+}
+
+impl<'a, 'b, R : 'a + AsyncRead + Unpin, ER : 'b + AsyncExtRead> AsyncMCompR<'a,'b,R,ER> {
+
+  /// next layers header first (see AsyncMCompW)
+  fn poll_read_header(&mut self, cx : &mut Context) -> Poll<Result<()>> {
+    if self.1.is_empty() {
+      return Poll::Ready(Ok(()));
+    }
+    if let CompRState::Initial = self.2[0] {
+      if self.1.len() > 1 {
+        if let Some((f,last)) = self.1.split_first_mut() {
+          let mut el = AsyncMCompR(&mut *self.0, last, &mut self.2[1..]);
+          try_ready!(el.poll_read_header(cx));
+          try_ready!(f.poll_read_header(cx, &mut el));
+        }
+      } else {
+        try_ready!(self.1[0].poll_read_header(cx, self.0));
+      }
+      self.2[0] = CompRState::HeadRead;
+    }
+    Poll::Ready(Ok(()))
+  }
+
+  /// layer state switched before next layers end (see AsyncMCompW)
+  fn poll_read_end(&mut self, cx : &mut Context) -> Poll<Result<()>> {
+    if let (Some((f,last)), Some((state,last_state))) = (self.1.split_first_mut(), self.2.split_first_mut()) {
+      if !last.is_empty() {
+        let mut el = AsyncMCompR(&mut *self.0, last, last_state);
+        if let CompRState::HeadRead = *state {
+          try_ready!(f.poll_read_end(cx, &mut el));
+          *state = CompRState::Initial;
+        }
+        try_ready!(el.poll_read_end(cx));
+      } else if let CompRState::HeadRead = *state {
+        // last
+        try_ready!(f.poll_read_end(cx, self.0));
+        *state = CompRState::Initial;
+      }
+    }
+    Poll::Ready(Ok(()))
+  }
+
+}
+
+impl<'a, 'b, W : 'a + AsyncWrite + Unpin, EW : 'b + AsyncExtWrite> AsyncWrite for AsyncMCompW<'a,'b,W,EW> {
+  fn poll_write(self : Pin<&mut Self>, cx : &mut Context, cont : &[u8]) -> Poll<Result<usize>> {
+    let this = self.get_mut();
+    if this.1.is_empty() {
+      return Pin::new(&mut *this.0).poll_write(cx, cont);
+    }
+    try_ready!(this.poll_write_header(cx));
+    if this.1.len() > 1 {
+      if let Some((f,last)) = this.1.split_first_mut() {
+        let mut el = AsyncMCompW(&mut *this.0, last, &mut this.2[1..]);
+        return f.poll_write_into(cx, &mut el, cont);
+      }
+    }
+    // last
+    this.1[0].poll_write_into(cx, this.0, cont)
+  }
+
+  fn poll_flush(self : Pin<&mut Self>, cx : &mut Context) -> Poll<Result<()>> {
+    let this = self.get_mut();
+    if this.1.is_empty() {
+      return Pin::new(&mut *this.0).poll_flush(cx);
+    }
+    if this.1.len() > 1 {
+      if let Some((f,last)) = this.1.split_first_mut() {
+        let mut el = AsyncMCompW(&mut *this.0, last, &mut this.2[1..]);
+        return f.poll_flush_into(cx, &mut el);
+      }
+    }
+    // last
+    try_ready!(this.1[0].poll_flush_into(cx, this.0));
+    Pin::new(&mut *this.0).poll_flush(cx)
+  }
+
+  /// technical inner writer : closing is only flushing
+  fn poll_close(self : Pin<&mut Self>, cx : &mut Context) -> Poll<Result<()>> {
+    self.poll_flush(cx)
+  }
+}
+
+impl<'a, 'b, R : 'a + AsyncRead + Unpin, ER : 'b + AsyncExtRead> AsyncRead for AsyncMCompR<'a,'b,R,ER> {
+  fn poll_read(self : Pin<&mut Self>, cx : &mut Context, buf : &mut [u8]) -> Poll<Result<usize>> {
+    let this = self.get_mut();
+    if this.1.is_empty() {
+      return Pin::new(&mut *this.0).poll_read(cx, buf);
+    }
+    try_ready!(this.poll_read_header(cx));
+    if this.1.len() > 1 {
+      if let Some((f,last)) = this.1.split_first_mut() {
+        let mut el = AsyncMCompR(&mut *this.0, last, &mut this.2[1..]);
+        return f.poll_read_from(cx, &mut el, buf);
+      }
+    }
+    // last
+    this.1[0].poll_read_from(cx, this.0, buf)
+  }
+}
+
+impl<EW : AsyncExtWrite> MultiWExt<EW> {
+  #[inline]
+  fn async_inner<'c,'b, W : AsyncWrite + Unpin>(&'c mut self, w : &'b mut W) -> AsyncMCompW<'b,'c,W,EW> {
+    AsyncMCompW(w,&mut self.0[..],&mut self.1[..])
+  }
+}
+
+impl<ER : AsyncExtRead> MultiRExt<ER> {
+  #[inline]
+  fn async_inner<'c,'b, R : AsyncRead + Unpin>(&'c mut self, r : &'b mut R) -> AsyncMCompR<'b,'c,R,ER> {
+    AsyncMCompR(r,&mut self.0[..],&mut self.1[..])
+  }
+}
+
+impl<EW : AsyncExtWrite> AsyncExtWrite for MultiWExt<EW> {
+  #[inline]
+  fn poll_write_header<W : AsyncWrite + Unpin>(&mut self, cx : &mut Context, w : &mut W) -> Poll<Result<()>> {
+    self.async_inner(w).poll_write_header(cx)
+  }
+  #[inline]
+  fn poll_write_into<W : AsyncWrite + Unpin>(&mut self, cx : &mut Context, w : &mut W, cont : &[u8]) -> Poll<Result<usize>> {
+    Pin::new(&mut self.async_inner(w)).poll_write(cx, cont)
+  }
+  #[inline]
+  fn poll_flush_into<W : AsyncWrite + Unpin>(&mut self, cx : &mut Context, w : &mut W) -> Poll<Result<()>> {
+    Pin::new(&mut self.async_inner(w)).poll_flush(cx)
+  }
+  #[inline]
+  fn poll_write_end<W : AsyncWrite + Unpin>(&mut self, cx : &mut Context, w : &mut W) -> Poll<Result<()>> {
+    self.async_inner(w).poll_write_end(cx)
+  }
+}
+
+impl<ER : AsyncExtRead> AsyncExtRead for MultiRExt<ER> {
+  #[inline]
+  fn poll_read_header<R : AsyncRead + Unpin>(&mut self, cx : &mut Context, r : &mut R) -> Poll<Result<()>> {
+    self.async_inner(r).poll_read_header(cx)
+  }
+  #[inline]
+  fn poll_read_from<R : AsyncRead + Unpin>(&mut self, cx : &mut Context, r : &mut R, buf : &mut[u8]) -> Poll<Result<usize>> {
+    Pin::new(&mut self.async_inner(r)).poll_read(cx, buf)
+  }
+  #[inline]
+  fn poll_read_end<R : AsyncRead + Unpin>(&mut self, cx : &mut Context, r : &mut R) -> Poll<Result<()>> {
+    self.async_inner(r).poll_read_end(cx)
+  }
+}
+
+impl AsyncExtWrite for ID {
+  #[inline]
+  fn poll_write_header<W : AsyncWrite + Unpin>(&mut self, _ : &mut Context, _ : &mut W) -> Poll<Result<()>> {
+    Poll::Ready(Ok(()))
+  }
+  #[inline]
+  fn poll_write_into<W : AsyncWrite + Unpin>(&mut self, cx : &mut Context, w : &mut W, cont : &[u8]) -> Poll<Result<usize>> {
+    Pin::new(w).poll_write(cx, cont)
+  }
+  #[inline]
+  fn poll_flush_into<W : AsyncWrite + Unpin>(&mut self, cx : &mut Context, w : &mut W) -> Poll<Result<()>> {
+    Pin::new(w).poll_flush(cx)
+  }
+  #[inline]
+  fn poll_write_end<W : AsyncWrite + Unpin>(&mut self, _ : &mut Context, _ : &mut W) -> Poll<Result<()>> {
+    Poll::Ready(Ok(()))
+  }
+}
+
+impl AsyncExtRead for ID {
+  #[inline]
+  fn poll_read_header<R : AsyncRead + Unpin>(&mut self, _ : &mut Context, _ : &mut R) -> Poll<Result<()>> {
+    Poll::Ready(Ok(()))
+  }
+  #[inline]
+  fn poll_read_from<R : AsyncRead + Unpin>(&mut self, cx : &mut Context, r : &mut R, buf : &mut[u8]) -> Poll<Result<usize>> {
+    Pin::new(r).poll_read(cx, buf)
+  }
+  #[inline]
+  fn poll_read_end<R : AsyncRead + Unpin>(&mut self, _ : &mut Context, _ : &mut R) -> Poll<Result<()>> {
+    Poll::Ready(Ok(()))
+  }
+}
@@ -0,0 +1,174 @@
+//! Object safe variants of ExtWrite and ExtRead.
+//!
+//! ExtWrite and ExtRead methods are generic over the inner writer or reader, so layers could not
+//! be used as trait objects. DynExtWrite and DynExtRead use `&mut dyn Write` and `&mut dyn Read`
+//! instead and are implemented for every ExtWrite and ExtRead.
+//! A boxed DynExtWrite (or DynExtRead) is an ExtWrite (or ExtRead), so layers of different types
+//! chosen at runtime could be stacked with `DynMultiWExt` and `DynMultiRExt` (at the cost of a
+//! virtual call per layer).
+
+use std::io::{
+  Write,
+  Read,
+  Result,
+  IoSlice,
+  IoSliceMut,
+};
+use super::{
+  ExtWrite,
+  ExtRead,
+  MultiWExt,
+  MultiRExt,
+};
+
+/// Object safe ExtWrite (see ExtWrite for methods).
+pub trait DynExtWrite {
+  fn dyn_write_header(&mut self, &mut dyn Write) -> Result<()>;
+  fn dyn_write_into(&mut self, &mut dyn Write, &[u8]) -> Result<usize>;
+  fn dyn_write_vectored_into(&mut self, &mut dyn Write, &[IoSlice]) -> Result<usize>;
+  fn dyn_write_all_into(&mut self, &mut dyn Write, &[u8]) -> Result<()>;
+  fn dyn_flush_into(&mut self, &mut dyn Write) -> Result<()>;
+  fn dyn_flush_all_into(&mut self, &mut dyn Write) -> Result<()>;
+  fn dyn_write_end(&mut self, &mut dyn Write) -> Result<()>;
+}
+
+/// Object safe ExtRead (see ExtRead for methods).
+pub trait DynExtRead {
+  fn dyn_read_header(&mut self, &mut dyn Read) -> Result<()>;
+  fn dyn_read_from(&mut self, &mut dyn Read, &mut [u8]) -> Result<usize>;
+  fn dyn_read_vectored_from(&mut self, &mut dyn Read, &mut [IoSliceMut]) -> Result<usize>;
+  fn dyn_read_exact_from(&mut self, &mut dyn Read, &mut [u8]) -> Result<()>;
+  fn dyn_skip_from(&mut self, &mut dyn Read, usize) -> Result<usize>;
+  fn dyn_discard_to_end(&mut self, &mut dyn Read) -> Result<u64>;
+  fn dyn_read_end(&mut self, &mut dyn Read) -> Result<()>;
+}
+
+/// Layers of any types, boxed.
+pub type DynMultiWExt = MultiWExt<Box<dyn DynExtWrite>>;
+
+/// Layers of any types, boxed.
+pub type DynMultiRExt = MultiRExt<Box<dyn DynExtRead>>;
+
+impl<EW : ExtWrite> DynExtWrite for EW {
+  #[inline]
+  fn dyn_write_header(&mut self, mut w : &mut dyn Write) -> Result<()> {
+    self.write_header(&mut w)
+  }
+  #[inline]
+  fn dyn_write_into(&mut self, mut w : &mut dyn Write, cont : &[u8]) -> Result<usize> {
+    self.write_into(&mut w, cont)
+  }
+  #[inline]
+  fn dyn_write_vectored_into(&mut self, mut w : &mut dyn Write, bufs : &[IoSlice]) -> Result<usize> {
+    self.write_vectored_into(&mut w, bufs)
+  }
+  #[inline]
+  fn dyn_write_all_into(&mut self, mut w : &mut dyn Write, cont : &[u8]) -> Result<()> {
+    self.write_all_into(&mut w, cont)
+  }
+  #[inline]
+  fn dyn_flush_into(&mut self, mut w : &mut dyn Write) -> Result<()> {
+    self.flush_into(&mut w)
+  }
+  #[inline]
+  fn dyn_flush_all_into(&mut self, mut w : &mut dyn Write) -> Result<()> {
+    self.flush_all_into(&mut w)
+  }
+  #[inline]
+  fn dyn_write_end(&mut self, mut w : &mut dyn Write) -> Result<()> {
+    self.write_end(&mut w)
+  }
+}
+
+impl<ER : ExtRead> DynExtRead for ER {
+  #[inline]
+  fn dyn_read_header(&mut self, mut r : &mut dyn Read) -> Result<()> {
+    self.read_header(&mut r)
+  }
+  #[inline]
+  fn dyn_read_from(&mut self, mut r : &mut dyn Read, buf : &mut [u8]) -> Result<usize> {
+    self.read_from(&mut r, buf)
+  }
+  #[inline]
+  fn dyn_read_vectored_from(&mut self, mut r : &mut dyn Read, bufs : &mut [IoSliceMut]) -> Result<usize> {
+    self.read_vectored_from(&mut r, bufs)
+  }
+  #[inline]
+  fn dyn_read_exact_from(&mut self, mut r : &mut dyn Read, buf : &mut [u8]) -> Result<()> {
+    self.read_exact_from(&mut r, buf)
+  }
+  #[inline]
+  fn dyn_skip_from(&mut self, mut r : &mut dyn Read, n : usize) -> Result<usize> {
+    self.skip_from(&mut r, n)
+  }
+  #[inline]
+  fn dyn_discard_to_end(&mut self, mut r : &mut dyn Read) -> Result<u64> {
+    self.discard_to_end(&mut r)
+  }
+  #[inline]
+  fn dyn_read_end(&mut self, mut r : &mut dyn Read) -> Result<()> {
+    self.read_end(&mut r)
+  }
+}
+
+impl<'a> ExtWrite for Box<dyn DynExtWrite + 'a> {
+  #[inline]
+  fn write_header<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    (**self).dyn_write_header(w)
+  }
+  #[inline]
+  fn write_into<W : Write>(&mut self, w : &mut W, cont : &[u8]) -> Result<usize> {
+    (**self).dyn_write_into(w, cont)
+  }
+  #[inline]
+  fn write_vectored_into<W : Write>(&mut self, w : &mut W, bufs : &[IoSlice]) -> Result<usize> {
+    (**self).dyn_write_vectored_into(w, bufs)
+  }
+  #[inline]
+  fn write_all_into<W : Write>(&mut self, w : &mut W, cont : &[u8]) -> Result<()> {
+    (**self).dyn_write_all_into(w, cont)
+  }
+  #[inline]
+  fn flush_into<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    (**self).dyn_flush_into(w)
+  }
+  #[inline]
+  fn flush_all_into<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    (**self).dyn_flush_all_into(w)
+  }
+  #[inline]
+  fn write_end<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    (**self).dyn_write_end(w)
+  }
+}
+
+impl<'a> ExtRead for Box<dyn DynExtRead + 'a> {
+  #[inline]
+  fn read_header<R : Read>(&mut self, r : &mut R) -> Result<()> {
+    (**self).dyn_read_header(r)
+  }
+  #[inline]
+  fn read_from<R : Read>(&mut self, r : &mut R, buf : &mut [u8]) -> Result<usize> {
+    (**self).dyn_read_from(r, buf)
+  }
+  #[inline]
+  fn read_vectored_from<R : Read>(&mut self, r : &mut R, bufs : &mut [IoSliceMut]) -> Result<usize> {
+    (**self).dyn_read_vectored_from(r, bufs)
+  }
+  #[inline]
+  fn read_exact_from<R : Read>(&mut self, r : &mut R, buf : &mut [u8]) -> Result<()> {
+    (**self).dyn_read_exact_from(r, buf)
+  }
+  #[inline]
+  fn skip_from<R : Read>(&mut self, r : &mut R, n : usize) -> Result<usize> {
+    (**self).dyn_skip_from(r, n)
+  }
+  #[inline]
+  fn discard_to_end<R : Read>(&mut self, r : &mut R) -> Result<u64> {
+    (**self).dyn_discard_to_end(r)
+  }
+  #[inline]
+  fn read_end<R : Read>(&mut self, r : &mut R) -> Result<()> {
+    (**self).dyn_read_end(r)
+  }
+}
@@ -22,10 +22,22 @@
 //! allow things such as MultiW or MultiR where we got a final Writer or final Reader but an
 //! undefined number of ExtWriter and ExtRead (and still static type without fat pointer).
 //!
-//! WriteExt and ReadExt could be composed, using MultiW/R or CopmExtW/R.
+//! WriteExt and ReadExt could be composed, using MultiW/R or CopmExtW/R (Duplex compose pairs of
+//! layers implementing both with the same order for write and read).
 //!
 //! WriteExt and ReadExt could be used as standard Reader or Writer by using CompW or CompR, 
 //!
+//! With `async` feature, AsyncExtWrite and AsyncExtRead are similar traits over futures-io
+//! AsyncWrite and AsyncRead (AsyncCompW, AsyncCompR and multi layer composition are also
+//! available), see `async_ext` module.
+//!
+//! Static stacks of layers of different types could be build with `ext_stack!` macros (nested
+//! CompExtW and CompExtR), see `stack` module.
+//!
+//! DynExtWrite and DynExtRead are object safe variants (implemented for all ExtWrite and
+//! ExtRead), to stack layers of different types chosen at runtime (DynMultiWExt and
+//! DynMultiRExt), see `dyn_ext` module.
+//!
 //! Composition by creating CompW of CompW as Writer and CompW as WriterExt is not really
 //! encouraged (even if some test are included) due to difficulty to write header or end of message
 //! recursivly (the first component is seen as a Read or a Write). CompW should in priority as a
@@ -37,12 +49,12 @@
 //!
 //! Current issue with this crate are
 //! - drop cause panic in panic when read_end / write_end panic : leading to no clue about the issue
-//! - flush is not recursive : only the extWrite flush, the inner writer does not : TODO add a bool
-//! in flush_into to say if inner writer must be flush : that way on drop inner writer will not be
-//! flush (but still contain end_write), and have the composition flush flush all the way. Plus
-//! expose in object (CompW, MultiComp) this boolean. CompExtW is already recursive by default and
-//! should stay like that.
-//! : inner writer must flush at each layer. (MultiW flush until the inner writer of course)
+//!   (use `finish` to get the error, or set a `DropPolicy` on CompW/CompR, with `log` feature
+//!   errors in drop could be logged)
+//! - flush_into is not recursive : only the extWrite flush, the inner writer does not.
+//!   `flush_all_into` flush all the way (CompExtW and MultiWExt flush each layer then the inner
+//!   writer), and is used by CompW `flush`. On drop inner writer is not flush (but still contain
+//!   end_write).
 //! - flush and write_end semantic is tricky : flush means that no counterpart is needed in read
 //!   (we can flush anywhere without a failure from read) whereas write_end involve a read_end need.
 //!   Read_end is triggered manually with two cases :
@@ -65,9 +77,8 @@
 //!
 
 
-#![cfg_attr(feature="with-clippy", feature(plugin))]
-
-#![cfg_attr(feature="with-clippy", plugin(clippy))]
+#[macro_use]
+pub mod stack;
 
 #[cfg(test)]
 pub mod test;
@@ -75,15 +86,65 @@ pub mod test;
 #[cfg(test)]
 extern crate rand;
 
+#[cfg(feature="async")]
+extern crate futures_io;
+
+#[cfg(feature="log")]
+#[macro_use]
+extern crate log;
+
+pub mod message;
+
+pub use message::{
+  MessageWriter,
+  MessageReader,
+  Message,
+  PeekRead,
+};
+
+pub mod dyn_ext;
+
+pub use dyn_ext::{
+  DynExtWrite,
+  DynExtRead,
+  DynMultiWExt,
+  DynMultiRExt,
+};
+
+#[cfg(feature="async")]
+pub mod async_ext;
+
+#[cfg(feature="async")]
+pub use async_ext::{
+  AsyncExtWrite,
+  AsyncExtRead,
+  PollBuf,
+  AsyncCompW,
+  AsyncCompR,
+  AsyncMultiW,
+  AsyncMultiR,
+  new_async_multiw,
+  new_async_multir,
+};
+
+use std::io;
 use std::io::{
   Write,
   Read,
   Result,
   Error,
   ErrorKind,
+  IoSlice,
+  IoSliceMut,
 };
 use std::ops::Drop;
+use std::mem;
 use std::slice::Iter;
+use std::thread;
+use std::sync::{
+  Arc,
+  Mutex,
+};
 
 use std::rc::Rc;
 use std::cell::RefCell;
@@ -100,6 +161,13 @@ pub trait ExtWrite {
   /// write buffer.
   fn write_into<W : Write>(&mut self, &mut W, &[u8]) -> Result<usize>;
 
+  /// write multiple buffers, by default only the first non empty buffer is written (like
+  /// `Write::write_vectored` default).
+  fn write_vectored_into<W : Write>(&mut self, w : &mut W, bufs : &[IoSlice]) -> Result<usize> {
+    let buf = bufs.iter().find(|b| !b.is_empty()).map_or(&[][..], |b| &**b);
+    self.write_into(w, buf)
+  }
+
   /// write all
   fn write_all_into<W : Write>(&mut self, w : &mut W, mut buf : &[u8]) -> Result<()> {
     while !buf.is_empty() {
@@ -119,12 +187,31 @@ pub trait ExtWrite {
   #[inline]
   fn flush_into<W : Write>(&mut self, _ : &mut W) -> Result<()> {Ok(())}
 
+  /// flush recursivly : flush content then flush inner writer
+  #[inline]
+  fn flush_all_into<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    try!(self.flush_into(w));
+    w.flush()
+  }
+
   /// write content at the end of stream. Read will be able to read it with a call to read_end.
   /// To use in a pure read write context, this is call on CompW Drop and should generally not need to be called manually.
   /// When the outer element of composition is removed drop finalize its action.
   /// TODO currently not called by flush as read got no symetric function
   fn write_end<W : Write>(&mut self, &mut W) -> Result<()>;
 
+  /// write with self up to `switch_to_second`, then with next (symmetric with `ExtRead::chain`).
+  /// Named `chain_write` and not `chain` : a type implementing both ExtWrite and ExtRead (most
+  /// layers) would get an ambiguous `chain` call (and `Read::chain` for composers).
+  fn chain_write<'a, 'b, W : ExtWrite + 'b>(&'a mut self, next : &'b mut W) -> ChainExtWrite<'a,'b,Self,W> where Self: Sized + 'a {
+        ChainExtWrite { first: self, second: next, done_first: false, first_end_done : false, second_header_done : false }
+  }
+  /// chain with next header already written (symmetric with `ExtRead::chain_with_initialized`,
+  /// named as `chain_write`).
+  fn chain_write_with_initialized<'a, 'b, W : ExtWrite + 'b>(&'a mut self, next : &'b mut W) -> ChainExtWrite<'a,'b,Self,W> where Self: Sized + 'a {
+        ChainExtWrite { first: self, second: next, done_first: false, first_end_done : false, second_header_done : true }
+  }
+
 }
 
 /* cannot as wetmp need to be instantiated out of new last for ref : see if as ref...
@@ -145,6 +232,13 @@ pub trait ExtRead {
   /// read in buffer.
   fn read_from<R : Read>(&mut self, &mut R, &mut[u8]) -> Result<usize>;
 
+  /// read in multiple buffers, by default only the first non empty buffer is read (like
+  /// `Read::read_vectored` default).
+  fn read_vectored_from<R : Read>(&mut self, r : &mut R, bufs : &mut [IoSliceMut]) -> Result<usize> {
+    let buf = bufs.iter_mut().find(|b| !b.is_empty()).map_or(&mut [][..], |b| &mut **b);
+    self.read_from(r, buf)
+  }
+
   /// read exact
   fn read_exact_from<R : Read>(&mut self, r : &mut R, mut buf: &mut[u8]) -> Result<()> {
     while !buf.is_empty() {
@@ -168,6 +262,30 @@ pub trait ExtRead {
     while { self.read_from(r,buf)? != 0} {}
     self.read_end(r)
   }
+
+  /// skip up to `n` bytes of content (same as `read_from` without caller buffer), return the
+  /// number of bytes skipped, 0 if ended (or if `n` is 0).
+  /// Default implementation reads in a stack buffer, layers knowing their framing should
+  /// override it to avoid decoding skipped content.
+  fn skip_from<R : Read>(&mut self, r : &mut R, n : usize) -> Result<usize> {
+    let mut buf = [0;256];
+    let l = if n < buf.len() { n } else { buf.len() };
+    self.read_from(r, &mut buf[..l])
+  }
+
+  /// skip all remaining content (up to first no content skipped), return the number of bytes
+  /// skipped. `read_end` is not called.
+  fn discard_to_end<R : Read>(&mut self, r : &mut R) -> Result<u64> {
+    let mut tot = 0;
+    loop {
+      match self.skip_from(r, usize::MAX) {
+        Ok(0) => return Ok(tot),
+        Ok(n) => tot += n as u64,
+        Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+        Err(e) => return Err(e),
+      }
+    }
+  }
   /// read end bytes (and possibly update internal state).
   /// To use in a pure read write context, this is call on CompR Drop and should generally not need to be called manually.
   /// When the outer element of composition is removed drop finalize its action.
@@ -217,8 +335,16 @@ pub enum CompRState {
 /// For composing with full support for write_end, consider only one layer of CompW and composing 
 /// the ExtWrit with a CompExtW composition.
 ///
-pub struct CompW<'a, 'b, W : 'a + Write, EW : 'b + ExtWrite>(pub &'a mut W, pub &'b mut EW, pub CompWState);
-//pub struct CompWOwn<'a, W : 'a + Write, EW : ExtWrite>(pub &'a mut W, pub EW, pub CompWState);
+/// ExtWrite is only taken out by `suspend` (when no drop is needed), drop policy is set with
+/// `with_drop_policy`.
+pub struct CompW<'a, 'b, W : 'a + Write, EW : 'b + ExtWrite>(pub &'a mut W, Option<&'b mut EW>, pub CompWState, DropPolicy);
+
+/// CompW with owned ExtWrite and owned inner writer (inner writer could still be a reference as
+/// `&mut W` is Write), it can therefore be returned from a function or stored in a struct.
+///
+/// Drop does not exists for CompWOwn (owned content could not be returned), `finish` or `suspend`
+/// must be called to write the end of message.
+pub struct CompWOwn<W : Write, EW : ExtWrite>(pub W, pub EW, pub CompWState);
 
 /// inner struct for implemention just to apply method of sw in write
 /// This is not to be use directly as write but just to use write_into and flush_into
@@ -230,6 +356,10 @@ impl<'a, 'b, W : 'a + Write, EW : 'b + ExtWrite> Write for CompExtWInner<'a, 'b,
     self.1.write_into(self.0, cont)
   }
   #[inline]
+  fn write_vectored(&mut self, bufs: &[IoSlice]) -> Result<usize> {
+    self.1.write_vectored_into(self.0, bufs)
+  }
+  #[inline]
   fn flush(&mut self) -> Result<()> {
     self.1.flush_into(self.0)
   }
@@ -240,10 +370,108 @@ impl<'a, 'b, W : 'a + Write, EW : 'b + ExtWrite> Write for CompExtWInner<'a, 'b,
  
 }
 
+/// Write a frame header and (start of) content in a single `write_vectored` call, for framing
+/// layers. `hpos` is the number of header bytes already written, it is updated on each write so
+/// that after an error a new call resumes the header (header is done when `hpos` is header
+/// length). A partially written header is completed without content (0 is then returned),
+/// returned value is the number of content bytes written.
+pub fn write_with_header<W : Write>(w : &mut W, header : &[u8], hpos : &mut usize, cont : &[u8]) -> Result<usize> {
+  let hrem = header.len() - *hpos;
+  let n = loop {
+    match w.write_vectored(&[IoSlice::new(&header[*hpos..]), IoSlice::new(cont)]) {
+      Err(ref e) if e.kind() == ErrorKind::Interrupted => (),
+      r => break try!(r),
+    }
+  };
+  if n >= hrem {
+    *hpos = header.len();
+    return Ok(n - hrem);
+  }
+  if n == 0 {
+    return Err(Error::new(ErrorKind::WriteZero, "failed to write header"));
+  }
+  *hpos += n;
+  while *hpos < header.len() {
+    match w.write(&header[*hpos..]) {
+      Ok(0) => return Err(Error::new(ErrorKind::WriteZero, "failed to write header")),
+      Ok(n) => *hpos += n,
+      Err(ref e) if e.kind() == ErrorKind::Interrupted => (),
+      Err(e) => return Err(e),
+    }
+  }
+  Ok(0)
+}
+
+/// Write `buf` from `pos`, for framing layers writing a buffered frame. `pos` is updated on each
+/// write so that after an error a new call resumes the frame.
+pub fn write_all_from<W : Write>(w : &mut W, buf : &[u8], pos : &mut usize) -> Result<()> {
+  while *pos < buf.len() {
+    match w.write(&buf[*pos..]) {
+      Ok(0) => return Err(Error::new(ErrorKind::WriteZero, "failed to write frame")),
+      Ok(n) => *pos += n,
+      Err(ref e) if e.kind() == ErrorKind::Interrupted => (),
+      Err(e) => return Err(e),
+    }
+  }
+  Ok(())
+}
+
+/// Fill `buf` from `pos`, for framing layers reading a fixed length header. `pos` is updated on
+/// each read so that after an error a new call resumes the header, end of stream is an error.
+pub fn read_exact_from<R : Read>(r : &mut R, buf : &mut [u8], pos : &mut usize) -> Result<()> {
+  while *pos < buf.len() {
+    match r.read(&mut buf[*pos..]) {
+      Ok(0) => return Err(Error::new(ErrorKind::UnexpectedEof, "end of stream in frame header")),
+      Ok(n) => *pos += n,
+      Err(ref e) if e.kind() == ErrorKind::Interrupted => (),
+      Err(e) => return Err(e),
+    }
+  }
+  Ok(())
+}
+
+/// Read at most `rem` bytes of frame content (a single read), `rem` is updated. Return 0 only if
+/// `rem` or `buf` is empty, end of stream is an error.
+pub fn read_in_frame<R : Read>(r : &mut R, rem : &mut usize, buf : &mut [u8]) -> Result<usize> {
+  let l = if *rem < buf.len() { *rem } else { buf.len() };
+  if l == 0 {
+    return Ok(0);
+  }
+  loop {
+    match r.read(&mut buf[..l]) {
+      Ok(0) => return Err(Error::new(ErrorKind::UnexpectedEof, "end of stream in frame")),
+      Ok(rr) => {
+        *rem -= rr;
+        return Ok(rr);
+      },
+      Err(ref e) if e.kind() == ErrorKind::Interrupted => (),
+      Err(e) => return Err(e),
+    }
+  }
+}
+
+/// Drop at most `n` of the `rem` remaining bytes of a frame without copy in a caller buffer,
+/// `rem` is updated (also on error). Return the number of bytes dropped, end of stream is an
+/// error.
+pub fn skip_in_frame<R : Read>(r : &mut R, rem : &mut usize, n : usize) -> Result<usize> {
+  let l = if *rem < n { *rem } else { n };
+  let mut t = r.by_ref().take(l as u64);
+  let res = io::copy(&mut t, &mut io::sink());
+  let rr = l - t.limit() as usize;
+  *rem -= rr;
+  try!(res);
+  if rr == 0 && l > 0 {
+    return Err(Error::new(ErrorKind::UnexpectedEof, "end of stream in frame"));
+  }
+  Ok(rr)
+}
+
 /// Compose two ExtWrite in a single on with Owned ExtWrite.
 /// EW1 apply over EW2 meaning that EW2 is the external layer (ew2 header written first without
 /// applying ew1 over it and ew2 end written last without ew1 written over it and content written
 /// by first applying ew2 then ew1.
+/// No state is kept : when header or end is called again after an error, both layers are called
+/// again (for instance ew1 end must do nothing if already written).
 pub struct CompExtW<EW1 : ExtWrite, EW2 : ExtWrite>(pub EW1, pub EW2);
 
 impl<EW1 : ExtWrite, EW2 : ExtWrite> ExtWrite for CompExtW<EW1, EW2> {
@@ -257,6 +485,10 @@ impl<EW1 : ExtWrite, EW2 : ExtWrite> ExtWrite for CompExtW<EW1, EW2> {
     self.0.write_into(&mut CompExtWInner(w, &mut self.1),cont)
   }
   #[inline]
+  fn write_vectored_into<W : Write>(&mut self, w : &mut W, bufs : &[IoSlice]) -> Result<usize> {
+    self.0.write_vectored_into(&mut CompExtWInner(w, &mut self.1),bufs)
+  }
+  #[inline]
   fn write_all_into<W : Write>(&mut self, w : &mut W, cont : &[u8]) -> Result<()> {
     self.0.write_all_into(&mut CompExtWInner(w, &mut self.1),cont)
   }
@@ -266,6 +498,11 @@ impl<EW1 : ExtWrite, EW2 : ExtWrite> ExtWrite for CompExtW<EW1, EW2> {
     self.1.flush_into(w)
   }
   #[inline]
+  fn flush_all_into<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    try!(self.0.flush_into(&mut CompExtWInner(w, &mut self.1)));
+    self.1.flush_all_into(w)
+  }
+  #[inline]
   fn write_end<W : Write>(&mut self, w : &mut W) -> Result<()> {
     try!(self.0.write_end(&mut CompExtWInner(w, &mut self.1)));
     self.1.write_end(w)
@@ -274,9 +511,18 @@ impl<EW1 : ExtWrite, EW2 : ExtWrite> ExtWrite for CompExtW<EW1, EW2> {
 
 
 /// Base construct to build a read upon another one (composable reader).
-pub struct CompR<'a, 'b, R : 'a + Read, ER : 'b + ExtRead>(pub &'a mut R, pub &'b mut ER, pub CompRState);
+/// ExtRead is only taken out by `suspend`, drop policy and auto read end mode are set with
+/// `with_drop_policy` and `with_auto_read_end`.
+pub struct CompR<'a, 'b, R : 'a + Read, ER : 'b + ExtRead>(pub &'a mut R, Option<&'b mut ER>, pub CompRState, DropPolicy, bool);
 
+/// CompR with owned ExtRead and owned inner reader (see CompWOwn).
+///
+/// Drop does not exists for CompROwn, `finish` or `suspend` must be called to read the end of
+/// message.
+pub struct CompROwn<R : Read, ER : ExtRead>(pub R, pub ER, pub CompRState, bool);
 
+/// Read counterpart of `CompExtW` (same layers order), with the same limitation when header or end
+/// are called again after an error.
 pub struct CompExtR<EW1 : ExtRead, EW2 : ExtRead>(pub EW1, pub EW2);
 impl<EW1 : ExtRead, EW2 : ExtRead> ExtRead for CompExtR<EW1, EW2> {
   #[inline]
@@ -290,11 +536,21 @@ impl<EW1 : ExtRead, EW2 : ExtRead> ExtRead for CompExtR<EW1, EW2> {
     self.0.read_from(&mut CompExtRInner(r, &mut self.1),buf)
   }
 
+  #[inline]
+  fn read_vectored_from<R : Read>(&mut self, r : &mut R, bufs : &mut [IoSliceMut]) -> Result<usize> {
+    self.0.read_vectored_from(&mut CompExtRInner(r, &mut self.1),bufs)
+  }
+
   #[inline]
   fn read_exact_from<R : Read>(&mut self, r : &mut R, buf: &mut[u8]) -> Result<()> {
     self.0.read_exact_from(&mut CompExtRInner(r, &mut self.1),buf)
   }
 
+  #[inline]
+  fn skip_from<R : Read>(&mut self, r : &mut R, n : usize) -> Result<usize> {
+    self.0.skip_from(&mut CompExtRInner(r, &mut self.1),n)
+  }
+
   #[inline]
   fn read_end<R : Read>(&mut self, r : &mut R) -> Result<()> {
     try!(self.0.read_end(&mut CompExtRInner(r, &mut self.1)));
@@ -303,6 +559,25 @@ impl<EW1 : ExtRead, EW2 : ExtRead> ExtRead for CompExtR<EW1, EW2> {
 
 }
 
+/// Writer and reader halves of a composition of layers implementing both ExtWrite and ExtRead
+/// (same order, see `Duplex`). Layers of the other half are clones, layers keeping per message
+/// state, split must be done between messages.
+impl<L1 : ExtWrite + ExtRead + Clone, L2 : ExtWrite + ExtRead + Clone> CompExtW<L1, L2> {
+  #[inline]
+  pub fn split(self) -> (CompExtW<L1, L2>, CompExtR<L1, L2>) {
+    let r = CompExtR(self.0.clone(), self.1.clone());
+    (self, r)
+  }
+}
+
+/// see `CompExtW` `split`
+impl<L1 : ExtWrite + ExtRead + Clone, L2 : ExtWrite + ExtRead + Clone> CompExtR<L1, L2> {
+  #[inline]
+  pub fn split(self) -> (CompExtW<L1, L2>, CompExtR<L1, L2>) {
+    (CompExtW(self.0.clone(), self.1.clone()), self)
+  }
+}
+
 /// Inner construct to build a read upon another one, do not use as write if you need automatic
 /// header or automatic end (technical).
 pub struct CompExtRInner<'a, 'b, R : 'a + Read, ER : 'b + ExtRead>(pub &'a mut R, pub &'b mut ER);
@@ -313,38 +588,173 @@ impl<'a, 'b, R : 'a + Read, ER : 'b + ExtRead> Read for CompExtRInner<'a,'b,R,ER
     self.1.read_from(self.0, buf)
   }
 
+  #[inline]
+  fn read_vectored(&mut self, bufs: &mut [IoSliceMut]) -> Result<usize> {
+    self.1.read_vectored_from(self.0, bufs)
+  }
+
   #[inline]
   fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
     self.1.read_exact_from(self.0, buf)
   }
 }
 
+/// Pair of layers composed with the same order for write and read : `Duplex::new((a, b), (c, d))`
+/// writes as `CompExtW(a, b)` and reads as `CompExtR(c, d)` (second layer is the external one).
+/// Layers keep per direction state, so write and read use their own instances (for layers
+/// implementing both ExtWrite and ExtRead, as most symmetric layers do).
+pub struct Duplex<L1 : ExtWrite + ExtRead, L2 : ExtWrite + ExtRead>(pub CompExtW<L1, L2>, pub CompExtR<L1, L2>);
+
+impl<L1 : ExtWrite + ExtRead, L2 : ExtWrite + ExtRead> Duplex<L1, L2> {
+  #[inline]
+  pub fn new(writer : (L1, L2), reader : (L1, L2)) -> Self {
+    Duplex(CompExtW(writer.0, writer.1), CompExtR(reader.0, reader.1))
+  }
+  /// layers for write and for read are build by `f`
+  #[inline]
+  pub fn from_fn<F : FnMut() -> (L1, L2)>(mut f : F) -> Self {
+    let w = f();
+    Duplex::new(w, f())
+  }
+  #[inline]
+  pub fn writer(&mut self) -> &mut CompExtW<L1, L2> {
+    &mut self.0
+  }
+  #[inline]
+  pub fn reader(&mut self) -> &mut CompExtR<L1, L2> {
+    &mut self.1
+  }
+  #[inline]
+  pub fn into_writer(self) -> CompExtW<L1, L2> {
+    self.0
+  }
+  #[inline]
+  pub fn into_reader(self) -> CompExtR<L1, L2> {
+    self.1
+  }
+  /// writer and reader with same order.
+  #[inline]
+  pub fn split(self) -> (CompExtW<L1, L2>, CompExtR<L1, L2>) {
+    (self.0, self.1)
+  }
+}
+
+impl<L1 : ExtWrite + ExtRead, L2 : ExtWrite + ExtRead> ExtWrite for Duplex<L1, L2> {
+  #[inline]
+  fn write_header<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    self.writer().write_header(w)
+  }
+  #[inline]
+  fn write_into<W : Write>(&mut self, w : &mut W, cont : &[u8]) -> Result<usize> {
+    self.writer().write_into(w, cont)
+  }
+  #[inline]
+  fn write_vectored_into<W : Write>(&mut self, w : &mut W, bufs : &[IoSlice]) -> Result<usize> {
+    self.writer().write_vectored_into(w, bufs)
+  }
+  #[inline]
+  fn write_all_into<W : Write>(&mut self, w : &mut W, cont : &[u8]) -> Result<()> {
+    self.writer().write_all_into(w, cont)
+  }
+  #[inline]
+  fn flush_into<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    self.writer().flush_into(w)
+  }
+  #[inline]
+  fn flush_all_into<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    self.writer().flush_all_into(w)
+  }
+  #[inline]
+  fn write_end<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    self.writer().write_end(w)
+  }
+}
+
+impl<L1 : ExtWrite + ExtRead, L2 : ExtWrite + ExtRead> ExtRead for Duplex<L1, L2> {
+  #[inline]
+  fn read_header<R : Read>(&mut self, r : &mut R) -> Result<()> {
+    self.reader().read_header(r)
+  }
+  #[inline]
+  fn read_from<R : Read>(&mut self, r : &mut R, buf : &mut[u8]) -> Result<usize> {
+    self.reader().read_from(r, buf)
+  }
+  #[inline]
+  fn read_vectored_from<R : Read>(&mut self, r : &mut R, bufs : &mut [IoSliceMut]) -> Result<usize> {
+    self.reader().read_vectored_from(r, bufs)
+  }
+  #[inline]
+  fn read_exact_from<R : Read>(&mut self, r : &mut R, buf: &mut[u8]) -> Result<()> {
+    self.reader().read_exact_from(r, buf)
+  }
+  #[inline]
+  fn skip_from<R : Read>(&mut self, r : &mut R, n : usize) -> Result<usize> {
+    self.reader().skip_from(r, n)
+  }
+  #[inline]
+  fn read_end<R : Read>(&mut self, r : &mut R) -> Result<()> {
+    self.reader().read_end(r)
+  }
+}
+
+
+/// What to do with an error from `write_end`, `flush` or `read_end` when CompW or CompR is
+/// dropped (to catch the error directly, `finish` should be use instead of drop).
+#[derive(Clone,Default)]
+pub enum DropPolicy {
+  /// error is ignored (default)
+  #[default]
+  Ignore,
+  /// error is logged (`log` feature)
+  #[cfg(feature="log")]
+  Log,
+  /// error is stored in a shared slot (previous error in slot is overwritten)
+  Stash(Arc<Mutex<Option<Error>>>),
+  /// panic on error in debug build (ignored in release build or if already panicking)
+  PanicDebug,
+}
 
-// TODO non mandatory log dependancy and a version of this which log errors
 #[inline]
-fn result_in_drop(_ : Result<()>) {
+fn result_in_drop(policy : &DropPolicy, r : Result<()>) {
+  if let Err(e) = r {
+    match *policy {
+      DropPolicy::Ignore => (),
+      #[cfg(feature="log")]
+      DropPolicy::Log => error!("error on composer drop : {}", e),
+      DropPolicy::Stash(ref slot) => {
+        match slot.lock() {
+          Ok(mut slot) => *slot = Some(e),
+          Err(poisoned) => *poisoned.into_inner() = Some(e),
+        }
+      },
+      DropPolicy::PanicDebug => {
+        if cfg!(debug_assertions) && !thread::panicking() {
+          panic!("error on composer drop : {}", e);
+        }
+      },
+    }
+  }
 }
 
-/// drop finalize but without catching possible issue TODO error mgmt?? include logger ? Or upt to
-/// ExtWrite write_end implementation to be safe (return allways ok and synch over shared variable
-/// like arcmut or jus rc cell and have something else managing this error
+/// drop finalize, with errors managed by the composer `DropPolicy` (ignored by default).
 /// Drop is only for reference CompW, for Own compw drop does not exists and should no be used as 
 /// a Write.
 impl<'a, 'b, W : 'a + Write, EW : 'b + ExtWrite> Drop for CompW<'a,'b,W,EW> {
   fn drop(&mut self) {
     if let CompWState::HeadWritten = self.2 {
-      result_in_drop(self.write_end());
-      result_in_drop(self.flush());
+      let r = self.write_end().and_then(|_|self.flush_into());
+      result_in_drop(&self.3, r);
     }
   }
 }
 
 
-/// drop finalize but without catching possible issue TODO error mgmt?? include logger ?
+/// drop finalize, with errors managed by the composer `DropPolicy` (ignored by default).
 impl<'a, 'b, R : 'a + Read, ER : 'b + ExtRead> Drop for CompR<'a,'b,R,ER> {
   fn drop(&mut self) {
     if let CompRState::HeadRead = self.2 {
-      result_in_drop(self.read_end());
+      let r = self.read_end();
+      result_in_drop(&self.3, r);
     }
   }
 }
@@ -354,91 +764,173 @@ impl<'a, 'b, W : 'a + Write, EW : 'b + ExtWrite> CompW<'a,'b,W,EW> {
 
   #[inline]
   pub fn new(w : &'a mut W, ew : &'b mut EW) -> Self {
-    CompW(w,ew,CompWState::Initial)
+    CompW(w,Some(ew),CompWState::Initial,DropPolicy::default())
+  }
+
+  #[inline]
+  pub fn with_drop_policy(mut self, policy : DropPolicy) -> Self {
+    self.3 = policy;
+    self
   }
 
   #[inline]
   /// suspend write (inner writer is available again) but keep reference for subsequent write in same state
-  pub fn suspend(mut self) -> Result<(&'b mut EW, CompWState)> {
+  /// (drop policy is kept for `resume`).
+  pub fn suspend(mut self) -> Result<(&'b mut EW, CompWState, DropPolicy)> {
     // manually to catch error instead of drop
     if let CompWState::HeadWritten = self.2 {
       try!(self.write_end());
-      try!(self.flush());
+      try!(self.flush_into());
       self.2 = CompWState::Initial;
     }
-    Ok((self.1,self.2.clone()))
+    // drop does nothing in initial state
+    let ew = self.1.take().expect("ExtWrite only taken on suspend");
+    Ok((ew, self.2.clone(), mem::replace(&mut self.3, DropPolicy::Ignore)))
+  }
+
+  #[inline]
+  pub fn resume(with : &'a mut W, from : (&'b mut EW, CompWState, DropPolicy)) -> Self {
+    CompW(with, Some(from.0), from.1, from.2)
+  }
+
+  /// inner writer and ExtWrite
+  #[inline]
+  fn parts(&mut self) -> (&mut W, &mut EW) {
+    (&mut *self.0, self.1.as_mut().expect("ExtWrite only taken on suspend"))
   }
 
+  /// flush ExtWrite, not recursive (inner writer is not flushed)
   #[inline]
-  pub fn resume(with : &'a mut W, from : (&'b mut EW, CompWState)) -> Self {
-    CompW(with, from.0, from.1)
+  fn flush_into(&mut self) -> Result<()> {
+    let (w, ew) = self.parts();
+    ew.flush_into(w)
   }
 
   #[inline]
   pub fn write_end(&mut self) -> Result<()> {
     if let CompWState::HeadWritten = self.2 {
-      try!(self.1.write_end(self.0));
+      {
+        let (w, ew) = self.parts();
+        try!(ew.write_end(w));
+      }
       self.2 = CompWState::Initial;
     }
     Ok(())
   }
+
+  #[inline]
+  /// write end and flush, returning error instead of letting drop manage it.
+  /// As with drop and `suspend`, flush is not recursive : only ExtWrite is flushed (`flush_into`)
+  /// and not the inner writer (`flush` could be called before to flush it).
+  /// On error, drop will not try to write end again.
+  pub fn finish(mut self) -> Result<()> {
+    let r = self.write_end().and_then(|_|self.flush_into());
+    self.2 = CompWState::Initial;
+    r
+  }
+
   /*pub fn set_end(&mut self) {
     self.2 = CompRState::WriteEnd
   }*/
 
 
 }
-/*
-impl<'a, W : 'a + Write, EW : ExtWrite> CompWOwn<'a,W,EW> {
+
+impl<W : Write, EW : ExtWrite> CompWOwn<W,EW> {
+
   #[inline]
-  pub fn new(w : &'a mut W, ew : EW) -> Self {
+  pub fn new(w : W, ew : EW) -> Self {
     CompWOwn(w,ew,CompWState::Initial)
   }
+
   #[inline]
-  /// suspend write (inner writer is available again) but keep reference for subsequent write in same state
-  pub fn suspend(mut self) -> Result<(EW, CompWState)> {
-    // manually to catch error instead of drop
-    if let CompWState::HeadWritten = self.2 {
-      try!(self.write_end());
-      try!(self.flush());
-      self.2 = CompWState::Initial;
-    }
-    Ok((self.1,self.2))
+  /// suspend write (inner writer is returned) but keep ExtWrite for subsequent write in same state
+  pub fn suspend(mut self) -> Result<(W, (EW, CompWState))> {
+    try!(self.write_end());
+    try!(self.1.flush_into(&mut self.0));
+    Ok((self.0,(self.1,self.2)))
   }
+
   #[inline]
-  pub fn resume(with : &'a mut W, from : (EW, CompWState)) -> Self {
+  pub fn resume(with : W, from : (EW, CompWState)) -> Self {
     CompWOwn(with, from.0, from.1)
   }
+
   #[inline]
   pub fn write_end(&mut self) -> Result<()> {
     if let CompWState::HeadWritten = self.2 {
-      try!(self.1.write_end(self.0));
+      try!(self.1.write_end(&mut self.0));
       self.2 = CompWState::Initial;
     }
     Ok(())
   }
-}*/
+
+  #[inline]
+  /// write end and flush, then return inner writer and ExtWrite.
+  /// Flush is not recursive (see CompW `finish`).
+  pub fn finish(mut self) -> Result<(W, EW)> {
+    try!(self.write_end());
+    try!(self.1.flush_into(&mut self.0));
+    Ok((self.0,self.1))
+  }
+
+  /// inner writer and ExtWrite
+  #[inline]
+  fn parts(&mut self) -> (&mut W, &mut EW) {
+    (&mut self.0, &mut self.1)
+  }
+
+  #[inline]
+  /// return inner writer and ExtWrite without writing end of message.
+  pub fn into_inner(self) -> (W, EW, CompWState) {
+    (self.0,self.1,self.2)
+  }
+
+}
 
 impl<'a, 'b, R : 'a + Read, ER : 'b + ExtRead> CompR<'a,'b,R,ER> {
 
   #[inline]
   pub fn new(r : &'a mut R, er : &'b mut ER) -> Self {
-    CompR(r,er,CompRState::Initial)
+    CompR(r,Some(er),CompRState::Initial,DropPolicy::default(),false)
+  }
+
+  #[inline]
+  /// When ext reader return 0 (end of message), read end is called and next read will read a new
+  /// header : that way consecutive messages could be read with plain Read (a message per
+  /// `read_to_end`). Message end must be signaled by ext reader (a zero length read on a non
+  /// empty buffer). End of stream before the header of a new message is read as 0 (no error).
+  pub fn with_auto_read_end(mut self, auto : bool) -> Self {
+    self.4 = auto;
+    self
+  }
+
+  #[inline]
+  pub fn with_drop_policy(mut self, policy : DropPolicy) -> Self {
+    self.3 = policy;
+    self
   }
 
   #[inline]
-  pub fn suspend(mut self) -> Result<(&'b mut ER, CompRState)> {
+  /// suspend read (inner reader is available again), drop policy and auto read end mode are kept
+  /// for `resume`.
+  pub fn suspend(mut self) -> Result<(&'b mut ER, CompRState, DropPolicy, bool)> {
     // manually to catch error instead of drop
-    if let CompRState::HeadRead = self.2 {
-      try!(self.1.read_end(self.0));
-      self.2 = CompRState::Initial;
-    }
-    Ok((self.1,self.2.clone()))
+    try!(self.read_end());
+    // drop does nothing in initial state
+    let er = self.1.take().expect("ExtRead only taken on suspend");
+    Ok((er, self.2.clone(), mem::replace(&mut self.3, DropPolicy::Ignore), self.4))
   }
 
   #[inline]
-  pub fn resume(with : &'a mut R, from : (&'b mut ER, CompRState)) -> Self {
-    CompR(with, from.0, from.1)
+  pub fn resume(with : &'a mut R, from : (&'b mut ER, CompRState, DropPolicy, bool)) -> Self {
+    CompR(with, Some(from.0), from.1, from.2, from.3)
+  }
+
+  /// inner reader and ExtRead
+  #[inline]
+  fn parts(&mut self) -> (&mut R, &mut ER) {
+    (&mut *self.0, self.1.as_mut().expect("ExtRead only taken on suspend"))
   }
 
   #[inline]
@@ -446,12 +938,25 @@ impl<'a, 'b, R : 'a + Read, ER : 'b + ExtRead> CompR<'a,'b,R,ER> {
   pub fn read_end(&mut self) -> Result<()> {
 
     if let CompRState::HeadRead = self.2 {
-      try!(self.1.read_end(self.0));
+      {
+        let (r, er) = self.parts();
+        try!(er.read_end(r));
+      }
       self.2 = CompRState::Initial;
     }
  
     Ok(())
   }
+
+  #[inline]
+  /// read end, returning error instead of letting drop manage it.
+  /// On error, drop will not try to read end again.
+  pub fn finish(mut self) -> Result<()> {
+    let r = self.read_end();
+    self.2 = CompRState::Initial;
+    r
+  }
+
 /*
   /// we know that read is end but for any reason we could not call read_end
   /// so we flag as read end and subsequent read will only read end content
@@ -464,19 +969,97 @@ impl<'a, 'b, R : 'a + Read, ER : 'b + ExtRead> CompR<'a,'b,R,ER> {
 */
 
 }
+
+impl<R : Read, ER : ExtRead> CompROwn<R,ER> {
+
+  #[inline]
+  pub fn new(r : R, er : ER) -> Self {
+    CompROwn(r,er,CompRState::Initial,false)
+  }
+
+  #[inline]
+  /// see CompR `with_auto_read_end`
+  pub fn with_auto_read_end(mut self, auto : bool) -> Self {
+    self.3 = auto;
+    self
+  }
+
+  #[inline]
+  /// suspend read (inner reader is returned) but keep ExtRead (and auto read end mode) for
+  /// subsequent read in same state
+  pub fn suspend(mut self) -> Result<(R, (ER, CompRState, bool))> {
+    try!(self.read_end());
+    Ok((self.0,(self.1,self.2,self.3)))
+  }
+
+  #[inline]
+  pub fn resume(with : R, from : (ER, CompRState, bool)) -> Self {
+    CompROwn(with, from.0, from.1, from.2)
+  }
+
+  #[inline]
+  pub fn read_end(&mut self) -> Result<()> {
+    if let CompRState::HeadRead = self.2 {
+      try!(self.1.read_end(&mut self.0));
+      self.2 = CompRState::Initial;
+    }
+    Ok(())
+  }
+
+  #[inline]
+  /// read end, then return inner reader and ExtRead.
+  pub fn finish(mut self) -> Result<(R, ER)> {
+    try!(self.read_end());
+    Ok((self.0,self.1))
+  }
+
+  /// inner reader and ExtRead
+  #[inline]
+  fn parts(&mut self) -> (&mut R, &mut ER) {
+    (&mut self.0, &mut self.1)
+  }
+
+  #[inline]
+  /// return inner reader and ExtRead without reading end of message.
+  pub fn into_inner(self) -> (R, ER, CompRState) {
+    (self.0,self.1,self.2)
+  }
+
+}
+// `parts` : inner writer is either a reference or owned
 macro_rules! write_impl_comp {() => (
   fn write(&mut self, cont: &[u8]) -> Result<usize> {
     match self.2 {
       CompWState::Initial => {
-        try!(self.1.write_header(self.0));
+        {
+          let (w, ew) = self.parts();
+          try!(ew.write_header(w));
+        }
         self.2 = CompWState::HeadWritten;
       },
       CompWState::HeadWritten => (),
     };
-    self.1.write_into(self.0, cont)
+    let (w, ew) = self.parts();
+    ew.write_into(w, cont)
   }
+  fn write_vectored(&mut self, bufs: &[IoSlice]) -> Result<usize> {
+    match self.2 {
+      CompWState::Initial => {
+        {
+          let (w, ew) = self.parts();
+          try!(ew.write_header(w));
+        }
+        self.2 = CompWState::HeadWritten;
+      },
+      CompWState::HeadWritten => (),
+    };
+    let (w, ew) = self.parts();
+    ew.write_vectored_into(w, bufs)
+  }
+  /// flush all layers and inner writer
   fn flush(&mut self) -> Result<()> {
-    self.1.flush_into(self.0)
+    let (w, ew) = self.parts();
+    ew.flush_all_into(w)
   }
 )}
 
@@ -485,34 +1068,106 @@ macro_rules! write_impl_comp {() => (
 impl<'a, 'b, W : 'a + Write, EW : 'b + ExtWrite> Write for CompW<'a,'b,W,EW> {
   write_impl_comp!();
 }
-/*
-/// incomplet write : no write end support.
-impl<'a, W : 'a + Write, EW : ExtWrite> Write for CompWOwn<'a,W,EW> {
+
+/// incomplet write : no write end support (`finish` must be called).
+impl<W : Write, EW : ExtWrite> Write for CompWOwn<W,EW> {
   write_impl_comp!();
-}*/
+}
 
-/// TODO find a way to incorporate read end in Read of compR (for the moment you need to at least
-/// use set_end (CompR fn). On Read_0 : no. TODO study other read functions.
-impl<'a, 'b, R : 'a + Read, ER : 'b + ExtRead> Read for CompR<'a,'b,R,ER> {
+/// reader counting read bytes
+struct CountRead<'a, R : 'a + Read>(&'a mut R, usize);
+
+impl<'a, R : 'a + Read> Read for CountRead<'a, R> {
+  #[inline]
   fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+    let n = try!(self.0.read(buf));
+    self.1 += n;
+    Ok(n)
+  }
+}
+
+/// read header, in auto read end mode end of stream before any header byte is not an error (no
+/// more message : false is returned).
+fn read_header_auto<R : Read, ER : ExtRead>(er : &mut ER, r : &mut R, auto : bool) -> Result<bool> {
+  if !auto {
+    try!(er.read_header(r));
+    return Ok(true);
+  }
+  let mut cr = CountRead(r, 0);
+  match er.read_header(&mut cr) {
+    Err(ref e) if e.kind() == ErrorKind::UnexpectedEof && cr.1 == 0 => Ok(false),
+    r => r.map(|_|true),
+  }
+}
+
+// `parts` : inner reader is either a reference or owned, `$auto` is auto read end field
+macro_rules! read_impl_comp {($auto:tt) => (
+  fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+    let auto = self.$auto;
     match self.2 {
       CompRState::Initial => {
-          try!(self.1.read_header(self.0));
-//    panic!("dd");
+          {
+            let (r, er) = self.parts();
+            if !try!(read_header_auto(er, r, auto)) {
+              return Ok(0);
+            }
+          }
           self.2 = CompRState::HeadRead;
-//    panic!("dd");
       },
       CompRState::HeadRead => (),
-/*      CompRState::ReadEnd => {
-          try!(self.1.read_end(self.0));
-          try!(self.1.read_header(self.0));
+    };
+    let r = {
+      let (r, er) = self.parts();
+      try!(er.read_from(r, buf))
+    };
+    if r == 0 && auto && !buf.is_empty() {
+      {
+        let (r, er) = self.parts();
+        try!(er.read_end(r));
+      }
+      self.2 = CompRState::Initial;
+    }
+    Ok(r)
+  }
+  fn read_vectored(&mut self, bufs: &mut [IoSliceMut]) -> Result<usize> {
+    let auto = self.$auto;
+    match self.2 {
+      CompRState::Initial => {
+          {
+            let (r, er) = self.parts();
+            if !try!(read_header_auto(er, r, auto)) {
+              return Ok(0);
+            }
+          }
           self.2 = CompRState::HeadRead;
-      },*/
+      },
+      CompRState::HeadRead => (),
     };
-    self.1.read_from(self.0, buf)
+    let r = {
+      let (r, er) = self.parts();
+      try!(er.read_vectored_from(r, bufs))
+    };
+    if r == 0 && auto && bufs.iter().any(|b| !b.is_empty()) {
+      {
+        let (r, er) = self.parts();
+        try!(er.read_end(r));
+      }
+      self.2 = CompRState::Initial;
+    }
+    Ok(r)
   }
+)}
+
+/// Read end is not called by default (`read_end` or drop), unless auto read end mode is used
+/// (see `with_auto_read_end`).
+impl<'a, 'b, R : 'a + Read, ER : 'b + ExtRead> Read for CompR<'a,'b,R,ER> {
+  read_impl_comp!(4);
+}
+
+/// no read end support (`finish` must be called), unless auto read end mode is used.
+impl<R : Read, ER : ExtRead> Read for CompROwn<R,ER> {
+  read_impl_comp!(3);
 }
- 
 
 /*
 struct StreamShadow<'a, 'b, T : 'a + WriteTransportStream, S : 'b + Shadow>
@@ -556,9 +1211,8 @@ impl<'a, 'b, T : 'a + ReadTransportStream, S : 'b + Shadow> Read for ReadStreamS
 pub type MultiW<'a, 'b, W, EW> = CompW<'a,'b,W,MultiWExt<EW>>;
 //pub type MultiW<'a, 'b, W : 'a + Write, EW : 'b + ExtWrite> = CompW<'a,'b,W,MultiWExt<EW>>;
 
-pub struct MultiWExt<EW : ExtWrite>(Vec<EW>, Vec<CompWState>);
+pub struct MultiWExt<EW>(Vec<EW>, Vec<CompWState>);
 
-/// TODO fn to remove one layer with ok write end (similar to suspend but with)
 /// MCompW is using drop to write end (for write use).
 /// It does not drop (it drop but drop does not writeend) when used internally by playing on
 /// states.
@@ -573,7 +1227,7 @@ struct MCompW<'a, 'b, W : 'a + Write, EW : 'b + ExtWrite>(&'a mut W, &'b mut[EW]
 pub type MultiR<'a, 'b, R, ER> = CompR<'a,'b,R,MultiRExt<ER>>;
 //pub type MultiR<'a, 'b, R : 'a + Read, ER : 'b + ExtRead> = CompR<'a,'b,R,MultiRExt<ER>>;
 
-pub struct MultiRExt<ER : ExtRead>(Vec<ER>, Vec<CompRState>);
+pub struct MultiRExt<ER>(Vec<ER>, Vec<CompRState>);
 
 struct MCompR<'a, 'b, R : 'a + Read, ER : 'b + ExtRead>(&'a mut R, &'b mut[ER], &'b mut [CompRState]);
 
@@ -581,13 +1235,18 @@ impl<'a, 'b, W : 'a + Write, EW : 'b + ExtWrite> MCompW<'a,'b,W,EW> {
 
   #[inline]
   fn write_header(&mut self) -> Result<()> {
+    if self.1.is_empty() {
+      return Ok(());
+    }
     match self.2[0] {
       CompWState::Initial => {
         if self.1.len() > 1 {
           if let Some((f,last)) = self.1.split_first_mut() {
           let mut el = MCompW(self.0, last, &mut self.2[1..]); 
-          try!(f.write_header(&mut el));
+          // lower layers first (as on first write), so that on error only failed layers write
+          // their header again
           try!(el.write_header());
+          try!(f.write_header(&mut el));
 
         }} else {
           try!((self.1).get_mut(0).unwrap().write_header(self.0));
@@ -601,6 +1260,9 @@ impl<'a, 'b, W : 'a + Write, EW : 'b + ExtWrite> MCompW<'a,'b,W,EW> {
 
   #[inline]
   fn write_end(&mut self) -> Result<()> {
+    if self.1.is_empty() {
+      return Ok(());
+    }
     match self.2[0] {
       CompWState::HeadWritten => {
  
@@ -608,18 +1270,44 @@ impl<'a, 'b, W : 'a + Write, EW : 'b + ExtWrite> MCompW<'a,'b,W,EW> {
         if let Some((f,last)) = self.1.split_first_mut()  {
           let mut el = MCompW(self.0, last, &mut self.2[1..]);
           try!(f.write_end(&mut el));
-          try!(el.write_end());
         }
         } else {
           // last
           try!((self.1).get_mut(0).unwrap().write_end(self.0));
         };
         self.2[0] = CompWState::Initial;
-        Ok(())
+        self.write_lower_end()
 
       },
-      CompWState::Initial => Ok(()),
+      // lower layers may not be ended (error on a previous call)
+      CompWState::Initial => self.write_lower_end(),
+    }
+  }
+
+  #[inline]
+  fn write_lower_end(&mut self) -> Result<()> {
+    if self.1.len() > 1 {
+      MCompW(&mut *self.0, &mut self.1[1..], &mut self.2[1..]).write_end()
+    } else {
+      Ok(())
+    }
+  }
+
+  #[inline]
+  /// flush all layers then inner writer
+  fn flush_all(&mut self) -> Result<()> {
+    if self.1.is_empty() {
+      return self.0.flush();
+    }
+    if self.1.len() > 1 {
+    if let Some((f,last)) = self.1.split_first_mut()  {
+      let mut el = MCompW(self.0, last, &mut self.2[1..]);
+      try!(f.flush_into(&mut el));
+      return el.flush_all();
+    }
     }
+    // last
+    (self.1).get_mut(0).unwrap().flush_all_into(self.0)
   }
 
 }
@@ -629,33 +1317,50 @@ impl<'a, 'b, R : 'a + Read, ER : 'b + ExtRead> MCompR<'a,'b,R,ER> {
   #[inline]
   /// as there is no flush in read read end will be called out of Read interface
   pub fn read_end(&mut self) -> Result<()> {
+    if self.1.is_empty() {
+      return Ok(());
+    }
     match self.2[0] {
       CompRState::HeadRead => {
         if self.1.len() > 1 {
         if let Some((f,last)) = self.1.split_first_mut()  {
           let mut el = MCompR(self.0, last, &mut self.2[1..]);
           try!(f.read_end(&mut el));
-          try!(el.read_end());
         }
         } else {
           // last
           try!((self.1).get_mut(0).unwrap().read_end(self.0));
         };
         self.2[0] = CompRState::Initial;
-        Ok(())
+        self.read_lower_end()
       },
-      CompRState::Initial => Ok(()),
+      // lower layers may not be ended (error on a previous call)
+      CompRState::Initial => self.read_lower_end(),
+    }
+  }
+
+  #[inline]
+  fn read_lower_end(&mut self) -> Result<()> {
+    if self.1.len() > 1 {
+      MCompR(&mut *self.0, &mut self.1[1..], &mut self.2[1..]).read_end()
+    } else {
+      Ok(())
     }
   }
   #[inline]
   fn read_header(&mut self) -> Result<()> {
+    if self.1.is_empty() {
+      return Ok(());
+    }
     match self.2[0] {
       CompRState::Initial => {
         if self.1.len() > 1 {
           if let Some((f,last)) = self.1.split_first_mut() {
           let mut el = MCompR(self.0, last, &mut self.2[1..]); 
-          try!(f.read_header(&mut el));
+          // lower layers first (as on first read), so that on error only failed layers read
+          // their header again
           try!(el.read_header());
+          try!(f.read_header(&mut el));
 
         }} else {
           try!((self.1).get_mut(0).unwrap().read_header(self.0));
@@ -687,7 +1392,9 @@ pub fn new_multiw<'a, 'b, W : 'a + Write, EW : 'b + ExtWrite>
     CompW::new(w, Box::new(MultiWExt::new(ew)))
 }
 */
-impl<EW : ExtWrite> MultiWExt<EW> {
+// constructors and accessors do not require ExtWrite (layers may only implement the async
+// variant of the trait).
+impl<EW> MultiWExt<EW> {
   #[inline]
   pub fn inner_extwrites(&self) -> &[EW] {
     &self.0
@@ -697,10 +1404,6 @@ impl<EW : ExtWrite> MultiWExt<EW> {
     &mut self.0
   }
  
-  #[inline]
-  fn inner<'c,'b, W : Write>(&'c mut self, w : &'b mut W) -> MCompW<'b,'c,W,EW> {
-    MCompW(w,&mut self.0[..],&mut self.1[..])
-  }
   #[inline]
   pub fn new(ew : Vec<EW>) -> Self {
     let state = Self::init_state(&ew[..]);
@@ -711,10 +1414,51 @@ impl<EW : ExtWrite> MultiWExt<EW> {
   pub fn init_state(ew : &[EW]) -> Vec<CompWState> {
     vec![CompWState::Initial; ew.len()]
   }
+
+  /// error if a message is started (write end not done)
+  #[allow(clippy::io_other_error)]
+  fn check_ended(&self) -> Result<()> {
+    if self.1.iter().any(|s| matches!(*s, CompWState::HeadWritten)) {
+      return Err(Error::new(ErrorKind::Other, "layers could not change before message end"));
+    }
+    Ok(())
+  }
+
+  /// add a new external layer (applied after all other layers), only between messages.
+  pub fn push_layer(&mut self, ew : EW) -> Result<()> {
+    try!(self.check_ended());
+    self.0.push(ew);
+    self.1.push(CompWState::Initial);
+    Ok(())
+  }
+
+  /// remove external layer, only between messages.
+  pub fn pop_layer(&mut self) -> Result<Option<EW>> {
+    try!(self.check_ended());
+    self.1.pop();
+    Ok(self.0.pop())
+  }
+
+  /// replace layer at index `ix` (external layer last) and return previous layer, only between
+  /// messages.
+  pub fn replace_layer(&mut self, ix : usize, ew : EW) -> Result<EW> {
+    try!(self.check_ended());
+    if ix >= self.0.len() {
+      return Err(Error::new(ErrorKind::InvalidInput, "no layer at index"));
+    }
+    self.1[ix] = CompWState::Initial;
+    Ok(::std::mem::replace(&mut self.0[ix], ew))
+  }
 }
 
+impl<EW : ExtWrite> MultiWExt<EW> {
+  #[inline]
+  fn inner<'c,'b, W : Write>(&'c mut self, w : &'b mut W) -> MCompW<'b,'c,W,EW> {
+    MCompW(w,&mut self.0[..],&mut self.1[..])
+  }
+}
 
-impl<ER : ExtRead> MultiRExt<ER> {
+impl<ER> MultiRExt<ER> {
   #[inline]
   pub fn len(&self) -> usize {
     self.0.len()
@@ -724,10 +1468,6 @@ impl<ER : ExtRead> MultiRExt<ER> {
     self.0.iter()
   }
   #[inline]
-  fn inner<'c,'b,R : Read>(&'c mut self, r : &'b mut R) -> MCompR<'b,'c,R,ER> {
-    MCompR(r,&mut self.0[..],&mut self.1[..])
-  }
-  #[inline]
   pub fn new(ew : Vec<ER>) -> Self {
     let state = Self::init_state(&ew[..]);
     MultiRExt(ew,state)
@@ -736,11 +1476,56 @@ impl<ER : ExtRead> MultiRExt<ER> {
   pub fn init_state(ew : &[ER]) -> Vec<CompRState> {
     vec![CompRState::Initial; ew.len()]
   }
+
+  /// error if a message is started (read end not done)
+  #[allow(clippy::io_other_error)]
+  fn check_ended(&self) -> Result<()> {
+    if self.1.iter().any(|s| matches!(*s, CompRState::HeadRead)) {
+      return Err(Error::new(ErrorKind::Other, "layers could not change before message end"));
+    }
+    Ok(())
+  }
+
+  /// add a new external layer (read before all other layers), only between messages.
+  pub fn push_layer(&mut self, er : ER) -> Result<()> {
+    try!(self.check_ended());
+    self.0.push(er);
+    self.1.push(CompRState::Initial);
+    Ok(())
+  }
+
+  /// remove external layer, only between messages.
+  pub fn pop_layer(&mut self) -> Result<Option<ER>> {
+    try!(self.check_ended());
+    self.1.pop();
+    Ok(self.0.pop())
+  }
+
+  /// replace layer at index `ix` (external layer last) and return previous layer, only between
+  /// messages.
+  pub fn replace_layer(&mut self, ix : usize, er : ER) -> Result<ER> {
+    try!(self.check_ended());
+    if ix >= self.0.len() {
+      return Err(Error::new(ErrorKind::InvalidInput, "no layer at index"));
+    }
+    self.1[ix] = CompRState::Initial;
+    Ok(::std::mem::replace(&mut self.0[ix], er))
+  }
+}
+
+impl<ER : ExtRead> MultiRExt<ER> {
+  #[inline]
+  fn inner<'c,'b,R : Read>(&'c mut self, r : &'b mut R) -> MCompR<'b,'c,R,ER> {
+    MCompR(r,&mut self.0[..],&mut self.1[..])
+  }
 }
 
 
 impl<'a, 'b, W : 'a + Write, EW : 'b + ExtWrite> Write for MCompW<'a,'b,W,EW> {
   fn write(&mut self, cont: &[u8]) -> Result<usize> {
+    if self.1.is_empty() {
+      return self.0.write(cont);
+    }
     try!(self.write_header());
     if self.1.len() > 1 {
       if let Some((f,last)) = self.1.split_first_mut() {
@@ -753,8 +1538,26 @@ impl<'a, 'b, W : 'a + Write, EW : 'b + ExtWrite> Write for MCompW<'a,'b,W,EW> {
  
   }
 
+  fn write_vectored(&mut self, bufs: &[IoSlice]) -> Result<usize> {
+    if self.1.is_empty() {
+      return self.0.write_vectored(bufs);
+    }
+    try!(self.write_header());
+    if self.1.len() > 1 {
+      if let Some((f,last)) = self.1.split_first_mut() {
+        let mut el = MCompW(self.0, last, &mut self.2[1..]);
+        return f.write_vectored_into(&mut el, bufs);
+      }
+    }
+    // last
+    (self.1).get_mut(0).unwrap().write_vectored_into(self.0, bufs)
+  }
+
   /// flush all layer
   fn flush(&mut self) -> Result<()> {
+    if self.1.is_empty() {
+      return self.0.flush();
+    }
     if self.1.len() > 1 {
     if let Some((f,last)) = self.1.split_first_mut()  {
       let mut el = MCompW(self.0, last, &mut self.2[1..]);
@@ -768,6 +1571,9 @@ impl<'a, 'b, W : 'a + Write, EW : 'b + ExtWrite> Write for MCompW<'a,'b,W,EW> {
 }
 impl<'a, 'b, R : 'a + Read, ER : 'b + ExtRead> Read for MCompR<'a,'b,R,ER> {
   fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+    if self.1.is_empty() {
+      return self.0.read(buf);
+    }
     try!(self.read_header());
     if self.1.len() > 1 {
       if let Some((f,last)) = self.1.split_first_mut() {
@@ -778,6 +1584,39 @@ impl<'a, 'b, R : 'a + Read, ER : 'b + ExtRead> Read for MCompR<'a,'b,R,ER> {
     // last
     (self.1).get_mut(0).unwrap().read_from(self.0, buf)
   }
+
+  fn read_vectored(&mut self, bufs: &mut [IoSliceMut]) -> Result<usize> {
+    if self.1.is_empty() {
+      return self.0.read_vectored(bufs);
+    }
+    try!(self.read_header());
+    if self.1.len() > 1 {
+      if let Some((f,last)) = self.1.split_first_mut() {
+        let mut el = MCompR(self.0, last, &mut self.2[1..]);
+        return f.read_vectored_from(&mut el, bufs);
+      }
+    }
+    // last
+    (self.1).get_mut(0).unwrap().read_vectored_from(self.0, bufs)
+  }
+}
+
+impl<'a, 'b, R : 'a + Read, ER : 'b + ExtRead> MCompR<'a,'b,R,ER> {
+  /// skip content of first layer (lower layers are read)
+  fn skip(&mut self, n : usize) -> Result<usize> {
+    if self.1.is_empty() {
+      return DefaultID().skip_from(self.0, n);
+    }
+    try!(self.read_header());
+    if self.1.len() > 1 {
+      if let Some((f,last)) = self.1.split_first_mut() {
+        let mut el = MCompR(self.0, last, &mut self.2[1..]);
+        return f.skip_from(&mut el, n);
+      }
+    }
+    // last
+    (self.1).get_mut(0).unwrap().skip_from(self.0, n)
+  }
 }
 
 impl<EW : ExtWrite> ExtWrite for MultiWExt<EW> {
@@ -794,9 +1633,17 @@ impl<EW : ExtWrite> ExtWrite for MultiWExt<EW> {
     self.inner(w).write(cont)
   }
   #[inline]
+  fn write_vectored_into<W : Write>(&mut self, w : &mut W, bufs : &[IoSlice]) -> Result<usize> {
+    self.inner(w).write_vectored(bufs)
+  }
+  #[inline]
   fn flush_into<W : Write>(&mut self, w : &mut W) -> Result<()> {
     self.inner(w).flush()
   }
+  #[inline]
+  fn flush_all_into<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    self.inner(w).flush_all()
+  }
 }
 
 
@@ -807,6 +1654,14 @@ impl<EW : ExtRead> ExtRead for MultiRExt<EW> {
     self.inner(r).read(buf)
   }
   #[inline]
+  fn read_vectored_from<R : Read>(&mut self, r : &mut R, bufs : &mut [IoSliceMut]) -> Result<usize> {
+    self.inner(r).read_vectored(bufs)
+  }
+  #[inline]
+  fn skip_from<R : Read>(&mut self, r : &mut R, n : usize) -> Result<usize> {
+    self.inner(r).skip(n)
+  }
+  #[inline]
   fn read_header<R : Read>(&mut self, r : &mut R) -> Result<()> {
     self.inner(r).read_header()
   }
@@ -826,6 +1681,10 @@ impl ExtRead for ID {
     r.read(buf)
   }
   #[inline]
+  fn read_vectored_from<R : Read>(&mut self, r : &mut R, bufs : &mut [IoSliceMut]) -> Result<usize> {
+    r.read_vectored(bufs)
+  }
+  #[inline]
   fn read_header<R : Read>(&mut self, _ : &mut R) -> Result<()> {
     Ok(())
   }
@@ -854,10 +1713,18 @@ impl ExtWrite for ID {
     w.write(cont)
   }
   #[inline]
+  fn write_vectored_into<W : Write>(&mut self, w : &mut W, bufs : &[IoSlice]) -> Result<usize> {
+    w.write_vectored(bufs)
+  }
+  #[inline]
   fn flush_into<W : Write>(&mut self, w : &mut W) -> Result<()> {
     w.flush()
   }
   #[inline]
+  fn flush_all_into<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    w.flush()
+  }
+  #[inline]
   fn write_all_into<W : Write>(&mut self, w : &mut W, buf : &[u8]) -> Result<()> {
     w.write_all(buf)
   }
@@ -884,12 +1751,24 @@ impl<ER : ExtRead> ExtRead for RefCell<ER> {
     inner.read_from(r,buf)
   }
 
+  #[inline]
+  fn read_vectored_from<R : Read>(&mut self, r : &mut R, bufs : &mut [IoSliceMut]) -> Result<usize> {
+    let mut inner = try!(self.try_borrow_mut().map_err(|e|BorrowMutErr(e)));
+    inner.read_vectored_from(r,bufs)
+  }
+
   #[inline]
   fn read_exact_from<R : Read>(&mut self, r : &mut R, buf: &mut[u8]) -> Result<()> {
     let mut inner = try!(self.try_borrow_mut().map_err(|e|BorrowMutErr(e)));
     inner.read_exact_from(r,buf)
   }
 
+  #[inline]
+  fn skip_from<R : Read>(&mut self, r : &mut R, n : usize) -> Result<usize> {
+    let mut inner = try!(self.try_borrow_mut().map_err(|e|BorrowMutErr(e)));
+    inner.skip_from(r,n)
+  }
+
   #[inline]
   fn read_end<R : Read>(&mut self, r : &mut R) -> Result<()> {
     let mut inner = try!(self.try_borrow_mut().map_err(|e|BorrowMutErr(e)));
@@ -910,6 +1789,11 @@ impl<EW : ExtWrite> ExtWrite for RefCell<EW> {
     inner.write_into(w,cont)
   }
   #[inline]
+  fn write_vectored_into<W : Write>(&mut self, w : &mut W, bufs : &[IoSlice]) -> Result<usize> {
+    let mut inner = try!(self.try_borrow_mut().map_err(|e|BorrowMutErr(e)));
+    inner.write_vectored_into(w,bufs)
+  }
+  #[inline]
   fn write_all_into<W : Write>(&mut self, w : &mut W, cont : &[u8]) -> Result<()> {
     let mut inner = try!(self.try_borrow_mut().map_err(|e|BorrowMutErr(e)));
     inner.write_all_into(w,cont)
@@ -920,6 +1804,11 @@ impl<EW : ExtWrite> ExtWrite for RefCell<EW> {
     inner.flush_into(w)
   }
   #[inline]
+  fn flush_all_into<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    let mut inner = try!(self.try_borrow_mut().map_err(|e|BorrowMutErr(e)));
+    inner.flush_all_into(w)
+  }
+  #[inline]
   fn write_end<W : Write>(&mut self, w : &mut W) -> Result<()> {
     let mut inner = try!(self.try_borrow_mut().map_err(|e|BorrowMutErr(e)));
     inner.write_end(w)
@@ -939,12 +1828,24 @@ impl<ER : ExtRead> ExtRead for Rc<RefCell<ER>> {
     inner.read_from(r,buf)
   }
 
+  #[inline]
+  fn read_vectored_from<R : Read>(&mut self, r : &mut R, bufs : &mut [IoSliceMut]) -> Result<usize> {
+    let mut inner = try!(self.try_borrow_mut().map_err(|e|BorrowMutErr(e)));
+    inner.read_vectored_from(r,bufs)
+  }
+
   #[inline]
   fn read_exact_from<R : Read>(&mut self, r : &mut R, buf: &mut[u8]) -> Result<()> {
     let mut inner = try!(self.try_borrow_mut().map_err(|e|BorrowMutErr(e)));
     inner.read_exact_from(r,buf)
   }
 
+  #[inline]
+  fn skip_from<R : Read>(&mut self, r : &mut R, n : usize) -> Result<usize> {
+    let mut inner = try!(self.try_borrow_mut().map_err(|e|BorrowMutErr(e)));
+    inner.skip_from(r,n)
+  }
+
   #[inline]
   fn read_end<R : Read>(&mut self, r : &mut R) -> Result<()> {
     let mut inner = try!(self.try_borrow_mut().map_err(|e|BorrowMutErr(e)));
@@ -965,6 +1866,11 @@ impl<EW : ExtWrite> ExtWrite for Rc<RefCell<EW>> {
     inner.write_into(w,cont)
   }
   #[inline]
+  fn write_vectored_into<W : Write>(&mut self, w : &mut W, bufs : &[IoSlice]) -> Result<usize> {
+    let mut inner = try!(self.try_borrow_mut().map_err(|e|BorrowMutErr(e)));
+    inner.write_vectored_into(w,bufs)
+  }
+  #[inline]
   fn write_all_into<W : Write>(&mut self, w : &mut W, cont : &[u8]) -> Result<()> {
     let mut inner = try!(self.try_borrow_mut().map_err(|e|BorrowMutErr(e)));
     inner.write_all_into(w,cont)
@@ -975,13 +1881,17 @@ impl<EW : ExtWrite> ExtWrite for Rc<RefCell<EW>> {
     inner.flush_into(w)
   }
   #[inline]
+  fn flush_all_into<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    let mut inner = try!(self.try_borrow_mut().map_err(|e|BorrowMutErr(e)));
+    inner.flush_all_into(w)
+  }
+  #[inline]
   fn write_end<W : Write>(&mut self, w : &mut W) -> Result<()> {
     let mut inner = try!(self.try_borrow_mut().map_err(|e|BorrowMutErr(e)));
     inner.write_end(w)
   }
 }
 /// Chain two extreader, read end of first and header of second (if needed) as soon as it read 0 length content
-/// TODO test case!!
 pub struct ChainExtRead<'a, 'b, T : ExtRead + 'a, U : ExtRead + 'b> {
     first: &'a mut T,
     second: &'b mut U,
@@ -1025,6 +1935,20 @@ impl<'a, 'b, T : ExtRead + 'a, U : ExtRead + 'b> ExtRead for ChainExtRead<'a,'b,
     }
   }
 
+  fn read_vectored_from<R : Read>(&mut self, r : &mut R, bufs : &mut [IoSliceMut]) -> Result<usize> {
+    if !self.done_first {
+      let i = self.first.read_vectored_from(r,bufs)?;
+      if i == 0 {
+        self.switch_to_second(r)?;
+        self.second.read_vectored_from(r,bufs)
+      } else {
+        Ok(i)
+      }
+    } else {
+      self.second.read_vectored_from(r,bufs)
+    }
+  }
+
   fn read_exact_from<R : Read>(&mut self, r : &mut R, buf: &mut[u8]) -> Result<()> {
     let mut i = 0;
     if !self.done_first {
@@ -1042,6 +1966,20 @@ impl<'a, 'b, T : ExtRead + 'a, U : ExtRead + 'b> ExtRead for ChainExtRead<'a,'b,
     }
     self.second.read_exact_from(r,&mut buf[i..])
   }
+
+  fn skip_from<R : Read>(&mut self, r : &mut R, n : usize) -> Result<usize> {
+    if !self.done_first {
+      let i = self.first.skip_from(r,n)?;
+      if i == 0 && n != 0 {
+        self.switch_to_second(r)?;
+        self.second.skip_from(r,n)
+      } else {
+        Ok(i)
+      }
+    } else {
+      self.second.skip_from(r,n)
+    }
+  }
   
   fn read_end<R : Read>(&mut self, r : &mut R) -> Result<()> {
     if !self.done_first {
@@ -1055,6 +1993,99 @@ impl<'a, 'b, T : ExtRead + 'a, U : ExtRead + 'b> ExtRead for ChainExtRead<'a,'b,
   }
 }
 
+/// Chain two extwriter, write end of first and header of second (if needed) on `switch_to_second`
+/// (mirror of ChainExtRead : reader switch when first read returns 0).
+pub struct ChainExtWrite<'a, 'b, T : ExtWrite + 'a, U : ExtWrite + 'b> {
+    first: &'a mut T,
+    second: &'b mut U,
+    done_first: bool,
+    first_end_done : bool,
+    second_header_done : bool,
+}
+
+impl<'a, 'b, T : ExtWrite + 'a, U : ExtWrite + 'b> ChainExtWrite<'a,'b,T,U> {
+  pub fn in_first (&self) -> bool { !self.done_first }
+  pub fn in_second (&self) -> bool { self.done_first }
+
+  /// write end of first and header of second, next writes use second (no op if already
+  /// switched). On error, calling it again only redo the failed step.
+  pub fn switch_to_second<W : Write> (&mut self, w : &mut W) -> Result<()> {
+    if !self.done_first {
+      if !self.first_end_done {
+        self.first.write_end(w)?;
+        self.first_end_done = true;
+      }
+      if !self.second_header_done {
+        self.second.write_header(w)?;
+        self.second_header_done = true;
+      }
+      self.done_first = true;
+    }
+    Ok(())
+  }
+}
+
+impl<'a, 'b, T : ExtWrite + 'a, U : ExtWrite + 'b> ExtWrite for ChainExtWrite<'a,'b,T,U> {
+  #[inline]
+  fn write_header<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    self.first.write_header(w)
+  }
+
+  #[inline]
+  fn write_into<W : Write>(&mut self, w : &mut W, cont : &[u8]) -> Result<usize> {
+    if !self.done_first {
+      self.first.write_into(w,cont)
+    } else {
+      self.second.write_into(w,cont)
+    }
+  }
+
+  #[inline]
+  fn write_vectored_into<W : Write>(&mut self, w : &mut W, bufs : &[IoSlice]) -> Result<usize> {
+    if !self.done_first {
+      self.first.write_vectored_into(w,bufs)
+    } else {
+      self.second.write_vectored_into(w,bufs)
+    }
+  }
+
+  #[inline]
+  fn write_all_into<W : Write>(&mut self, w : &mut W, cont : &[u8]) -> Result<()> {
+    if !self.done_first {
+      self.first.write_all_into(w,cont)
+    } else {
+      self.second.write_all_into(w,cont)
+    }
+  }
+
+  #[inline]
+  fn flush_into<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    if !self.done_first {
+      self.first.flush_into(w)
+    } else {
+      self.second.flush_into(w)
+    }
+  }
+
+  #[inline]
+  fn flush_all_into<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    if !self.done_first {
+      self.first.flush_all_into(w)
+    } else {
+      self.second.flush_all_into(w)
+    }
+  }
+
+  fn write_end<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    self.switch_to_second(w)?;
+    self.second.write_end(w)?;
+    // reinit writer (costless)
+    self.done_first = false;
+    self.first_end_done = false;
+    self.second_header_done = false;
+    Ok(())
+  }
+}
 
 /// similar to ID but using default trait implementation
 pub struct DefaultID();
@@ -1104,6 +2135,10 @@ impl<'a, EW : ExtWrite> ExtWrite for &'a mut EW {
     (*self).write_into(w,cont)
   }
   #[inline]
+  fn write_vectored_into<W : Write>(&mut self, w : &mut W, bufs : &[IoSlice]) -> Result<usize> {
+    (*self).write_vectored_into(w,bufs)
+  }
+  #[inline]
   fn write_all_into<W : Write>(&mut self, w : &mut W, cont: &[u8]) -> Result<()> {
     (*self).write_all_into(w,cont)
   }
@@ -1111,6 +2146,43 @@ impl<'a, EW : ExtWrite> ExtWrite for &'a mut EW {
   fn flush_into<W : Write>(&mut self, w : &mut W) -> Result<()> {
     (*self).flush_into(w)
   }
+  #[inline]
+  fn flush_all_into<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    (*self).flush_all_into(w)
+  }
+}
+
+// same form as the `ExtWrite` implementation above
+#[allow(clippy::needless_lifetimes)]
+impl<'a, ER : ExtRead> ExtRead for &'a mut ER {
+  #[inline]
+  fn read_header<R : Read>(&mut self, r : &mut R) -> Result<()> {
+    (*self).read_header(r)
+  }
+  #[inline]
+  fn read_from<R : Read>(&mut self, r : &mut R, buf : &mut[u8]) -> Result<usize> {
+    (*self).read_from(r,buf)
+  }
+  #[inline]
+  fn read_vectored_from<R : Read>(&mut self, r : &mut R, bufs : &mut [IoSliceMut]) -> Result<usize> {
+    (*self).read_vectored_from(r,bufs)
+  }
+  #[inline]
+  fn read_exact_from<R : Read>(&mut self, r : &mut R, buf : &mut[u8]) -> Result<()> {
+    (*self).read_exact_from(r,buf)
+  }
+  #[inline]
+  fn skip_from<R : Read>(&mut self, r : &mut R, n : usize) -> Result<usize> {
+    (*self).skip_from(r,n)
+  }
+  #[inline]
+  fn discard_to_end<R : Read>(&mut self, r : &mut R) -> Result<u64> {
+    (*self).discard_to_end(r)
+  }
+  #[inline]
+  fn read_end<R : Read>(&mut self, r : &mut R) -> Result<()> {
+    (*self).read_end(r)
+  }
 }
 
 
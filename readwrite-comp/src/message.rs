@@ -0,0 +1,232 @@
+//! Message reading and writing over a framed stream.
+//!
+//! MessageWriter write consecutive messages (header, content and end of each message), and
+//! MessageReader read them back, either as a Read per message or as a bounded `Vec<u8>`.
+//! It works with any ExtRead signaling the end of message by returning 0 (for instance
+//! `SizedWindows` or `EscapeTerm`), ExtRead which does not are read until the end of stream.
+//! Message content is what the ExtRead returns (for instance `SizedWindows` padding is included).
+
+use std::io::{
+  Write,
+  Read,
+  Result,
+  Error,
+  ErrorKind,
+};
+use super::{
+  ExtWrite,
+  ExtRead,
+  CompR,
+  CompRState,
+  CompWOwn,
+  CompWState,
+  read_header_auto,
+};
+
+/// Write consecutive messages, each message being started by its header and finished by its end.
+pub struct MessageWriter<W : Write, EW : ExtWrite>(pub CompWOwn<W,EW>);
+
+impl<W : Write, EW : ExtWrite> MessageWriter<W,EW> {
+
+  #[inline]
+  pub fn new(w : W, ew : EW) -> Self {
+    MessageWriter(CompWOwn::new(w,ew))
+  }
+
+  /// write header of a new message, do nothing if message is already started (a write also start
+  /// a message).
+  pub fn start_message(&mut self) -> Result<()> {
+    if let CompWState::Initial = (self.0).2 {
+      try!((self.0).1.write_header(&mut (self.0).0));
+      (self.0).2 = CompWState::HeadWritten;
+    }
+    Ok(())
+  }
+
+  /// write end of message and flush (recursivly).
+  pub fn finish_message(&mut self) -> Result<()> {
+    try!(self.start_message());
+    try!(self.0.write_end());
+    self.0.flush()
+  }
+
+  /// write a full message
+  pub fn write_message(&mut self, m : &[u8]) -> Result<()> {
+    try!(self.start_message());
+    try!(self.0.write_all(m));
+    self.finish_message()
+  }
+
+  /// return inner writer and ExtWrite, current message is not finished.
+  #[inline]
+  pub fn into_inner(self) -> (W, EW) {
+    let (w, ew, _) = self.0.into_inner();
+    (w, ew)
+  }
+
+}
+
+impl<W : Write, EW : ExtWrite> Write for MessageWriter<W,EW> {
+  #[inline]
+  fn write(&mut self, cont: &[u8]) -> Result<usize> {
+    self.0.write(cont)
+  }
+  #[inline]
+  fn flush(&mut self) -> Result<()> {
+    self.0.flush()
+  }
+}
+
+/// Reader with one byte lookahead (to check end of stream before reading a message header).
+pub struct PeekRead<R : Read>(pub R, Option<u8>);
+
+impl<R : Read> PeekRead<R> {
+
+  #[inline]
+  pub fn new(r : R) -> Self {
+    PeekRead(r, None)
+  }
+
+  /// check if inner reader is at its end (a byte is read and kept for next read otherwhise)
+  pub fn at_end(&mut self) -> Result<bool> {
+    if self.1.is_some() {
+      return Ok(false);
+    }
+    let mut b = [0];
+    loop {
+      match self.0.read(&mut b) {
+        Ok(0) => return Ok(true),
+        Ok(_) => {
+          self.1 = Some(b[0]);
+          return Ok(false);
+        },
+        Err(ref e) if e.kind() == ErrorKind::Interrupted => (),
+        Err(e) => return Err(e),
+      }
+    }
+  }
+
+}
+
+impl<R : Read> Read for PeekRead<R> {
+  fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+    if buf.is_empty() {
+      return Ok(0);
+    }
+    if let Some(b) = self.1.take() {
+      buf[0] = b;
+      return Ok(1);
+    }
+    self.0.read(buf)
+  }
+}
+
+/// Read consecutive messages.
+pub struct MessageReader<R : Read, ER : ExtRead>(pub PeekRead<R>, pub ER);
+
+/// A single message, read returns 0 at the end of the message.
+/// On drop, unread content is skipped (errors are ignored), `finish` should be use to catch
+/// errors.
+pub struct Message<'a, R : 'a + Read, ER : 'a + ExtRead>(CompR<'a,'a,PeekRead<R>,ER>, bool);
+
+impl<R : Read, ER : ExtRead> MessageReader<R,ER> {
+
+  #[inline]
+  pub fn new(r : R, er : ER) -> Self {
+    MessageReader(PeekRead::new(r), er)
+  }
+
+  /// next message or None if inner reader is at its end.
+  pub fn next_message(&mut self) -> Result<Option<Message<'_,R,ER>>> {
+    if try!(self.0.at_end()) {
+      return Ok(None);
+    }
+    Ok(Some(Message(CompR::new(&mut self.0, &mut self.1).with_auto_read_end(true), false)))
+  }
+
+  /// read next message content, returning an error if content is bigger than `max_len` (message
+  /// is skipped in this case).
+  pub fn read_message(&mut self, max_len : usize) -> Result<Option<Vec<u8>>> {
+    match try!(self.next_message()) {
+      Some(mut m) => {
+        let mut res = Vec::new();
+        let l = try!((&mut m).take(max_len as u64 + 1).read_to_end(&mut res));
+        if l > max_len {
+          return Err(Error::new(ErrorKind::InvalidData, "message bigger than max length"));
+        }
+        try!(m.finish());
+        Ok(Some(res))
+      },
+      None => Ok(None),
+    }
+  }
+
+  /// return inner reader and ExtRead, a peeked byte (see `PeekRead`) is lost.
+  #[inline]
+  pub fn into_inner(self) -> (R, ER) {
+    ((self.0).0, self.1)
+  }
+
+}
+
+impl<'a, R : 'a + Read, ER : 'a + ExtRead> Message<'a,R,ER> {
+
+  /// skip unread content and read end of message.
+  /// On error, drop will not try to skip again.
+  pub fn finish(mut self) -> Result<()> {
+    let r = self.skip();
+    self.set_done();
+    r
+  }
+
+  /// nothing more is read, inner CompR state is reset so its drop does not read end again
+  fn set_done(&mut self) {
+    self.1 = true;
+    (self.0).2 = CompRState::Initial;
+  }
+
+  /// unread content is discarded by the ExtRead (without copy in a buffer), then end is read
+  fn skip(&mut self) -> Result<()> {
+    if self.1 {
+      return Ok(());
+    }
+    if let CompRState::Initial = (self.0).2 {
+      {
+        let (r, er) = self.0.parts();
+        if !try!(read_header_auto(er, r, true)) {
+          return Ok(());
+        }
+      }
+      (self.0).2 = CompRState::HeadRead;
+    }
+    {
+      let (r, er) = self.0.parts();
+      try!(er.discard_to_end(r));
+    }
+    self.0.read_end()
+  }
+
+}
+
+impl<'a, R : 'a + Read, ER : 'a + ExtRead> Read for Message<'a,R,ER> {
+  fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+    if self.1 {
+      return Ok(0);
+    }
+    let r = try!(self.0.read(buf));
+    if r == 0 && !buf.is_empty() {
+      // read end already done (auto read end)
+      self.1 = true;
+    }
+    Ok(r)
+  }
+}
+
+impl<'a, R : 'a + Read, ER : 'a + ExtRead> Drop for Message<'a,R,ER> {
+  fn drop(&mut self) {
+    if !self.1 {
+      let _ = self.skip();
+      self.set_done();
+    }
+  }
+}
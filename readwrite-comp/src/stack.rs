@@ -0,0 +1,51 @@
+//! Macros to build statically typed stacks of layers of different types (nested `CompExtW` and
+//! `CompExtR`).
+//!
+//! Layers are listed in the same order as for `MultiWExt` : first layer is applied first on
+//! content, external layer is last. For instance `ext_stack_w!(cipher, pad, frame)` is
+//! `CompExtW(cipher, CompExtW(pad, frame))`.
+//!
+//! `ext_stack!` build both the writer and the mirrored reader stack from the same list, so read
+//! order always matches write order.
+
+/// Writer stack from a list of ExtWrite (external layer last).
+#[macro_export(local_inner_macros)]
+macro_rules! ext_stack_w {
+  ($e:expr) => ($e);
+  ($e:expr, $($rest:expr),+ $(,)*) => ($crate::CompExtW($e, ext_stack_w!($($rest),+)));
+  ($e:expr,) => ($e);
+}
+
+/// Reader stack from a list of ExtRead (external layer last, same order as for `ext_stack_w!`).
+#[macro_export(local_inner_macros)]
+macro_rules! ext_stack_r {
+  ($e:expr) => ($e);
+  ($e:expr, $($rest:expr),+ $(,)*) => ($crate::CompExtR($e, ext_stack_r!($($rest),+)));
+  ($e:expr,) => ($e);
+}
+
+/// Type of a writer stack (`ext_stack_w_type!(A, B, C)` is `CompExtW<A, CompExtW<B, C>>`).
+#[macro_export(local_inner_macros)]
+macro_rules! ext_stack_w_type {
+  ($t:ty) => ($t);
+  ($t:ty, $($rest:ty),+ $(,)*) => ($crate::CompExtW<$t, ext_stack_w_type!($($rest),+)>);
+  ($t:ty,) => ($t);
+}
+
+/// Type of a reader stack (see `ext_stack_w_type!`).
+#[macro_export(local_inner_macros)]
+macro_rules! ext_stack_r_type {
+  ($t:ty) => ($t);
+  ($t:ty, $($rest:ty),+ $(,)*) => ($crate::CompExtR<$t, ext_stack_r_type!($($rest),+)>);
+  ($t:ty,) => ($t);
+}
+
+/// Writer and reader stacks `(ext_stack_w!(..), ext_stack_r!(..))` from a single list.
+///
+/// Either each item is a layer implementing both ExtWrite and ExtRead (the expression is then
+/// evaluated twice, once for each stack), or each item is `writer => reader`.
+#[macro_export(local_inner_macros)]
+macro_rules! ext_stack {
+  ($($w:expr => $r:expr),+ $(,)*) => ((ext_stack_w!($($w),+), ext_stack_r!($($r),+)));
+  ($($e:expr),+ $(,)*) => ((ext_stack_w!($($e),+), ext_stack_r!($($e),+)));
+}
@@ -9,12 +9,24 @@ use super::{
   CompExtW,
   CompW,
   CompR,
+  CompWOwn,
+  CompROwn,
+  DropPolicy,
+  MessageWriter,
+  MessageReader,
   //MultiW,
   //MultiR,
   MultiWExt,
   new_multiw,
   MultiRExt,
   new_multir,
+  ID,
+  write_with_header,
+  DynExtWrite,
+  DynExtRead,
+  DynMultiWExt,
+  DynMultiRExt,
+  Duplex,
 };
 
 use std::io::{
@@ -22,12 +34,19 @@ use std::io::{
   Read,
   Cursor,
   Result,
-  //Error,
-  //ErrorKind,
+  Error,
+  ErrorKind,
+  IoSlice,
+  IoSliceMut,
 };
+use std::sync::{
+  Arc,
+  Mutex,
+};
+use std::thread;
 
 use rand::thread_rng;
-use rand::Rng;
+use rand::RngCore;
 
 pub mod endstream;
 pub mod ciph;
@@ -376,6 +395,311 @@ fn test_suspend2() {
   //void.write(&[0]);
 }
 
+/// a composition writer and reader failing on end
+pub struct FailEnd;
+
+impl ExtWrite for FailEnd {
+  #[inline]
+  fn write_header<W : Write>(&mut self, _ : &mut W) -> Result<()> {Ok(())}
+  #[inline]
+  fn write_into<W : Write>(&mut self, w : &mut W, cont : &[u8]) -> Result<usize> {w.write(cont)}
+  #[inline]
+  fn write_end<W : Write>(&mut self, _ : &mut W) -> Result<()> {
+    Err(Error::new(ErrorKind::Other, "write end failure"))
+  }
+}
+impl ExtRead for FailEnd {
+  #[inline]
+  fn read_header<R : Read>(&mut self, _ : &mut R) -> Result<()> {Ok(())}
+  #[inline]
+  fn read_from<R : Read>(&mut self, r : &mut R, buf : &mut[u8]) -> Result<usize> {
+    r.read(buf)
+  }
+  #[inline]
+  fn read_end<R : Read>(&mut self, _ : &mut R) -> Result<()> {
+    Err(Error::new(ErrorKind::Other, "read end failure"))
+  }
+}
+
+#[test]
+fn test_finish() {
+  let mut w : Cuvec = Cursor::new(Vec::new());
+  let mut f = FailEnd;
+  let mut compw = CompW::new(&mut w, &mut f);
+  compw.write(&[0]).unwrap();
+  assert!(compw.finish().is_err());
+  let mut compw = CompW::new(&mut w, &mut f);
+  compw.write(&[0]).unwrap();
+  assert!(compw.suspend().is_err());
+  w.set_position(0);
+  let mut compr = CompR::new(&mut w, &mut f);
+  compr.read(&mut [0]).unwrap();
+  assert!(compr.finish().is_err());
+  let mut v = Void;
+  let compw = CompW::new(&mut w, &mut v);
+  compw.finish().unwrap();
+}
+
+fn check_send<T : Send>(_ : &T) {
+}
+
+#[test]
+fn test_drop_policy() {
+  let slot = Arc::new(Mutex::new(None));
+  let mut w : Cuvec = Cursor::new(Vec::new());
+  let mut f = FailEnd;
+  {
+    let mut compw = CompW::new(&mut w, &mut f).with_drop_policy(DropPolicy::Stash(slot.clone()));
+    compw.write(&[0,1]).unwrap();
+  }
+  assert!(slot.lock().unwrap().take().unwrap().kind() == ErrorKind::Other);
+  {
+    // nothing written : no end
+    let _ = CompW::new(&mut w, &mut f).with_drop_policy(DropPolicy::Stash(slot.clone()));
+  }
+  assert!(slot.lock().unwrap().is_none());
+  {
+    // policy kept by suspend and resume
+    let mut v = Void;
+    let state = CompW::new(&mut w, &mut v).with_drop_policy(DropPolicy::Stash(slot.clone()))
+      .suspend().unwrap();
+    let mut w2 : Cuvec = Cursor::new(Vec::new());
+    let (_, st, policy) = state;
+    let mut compw = CompW::resume(&mut w2, (&mut f, st, policy));
+    compw.write(&[0]).unwrap();
+  }
+  assert!(slot.lock().unwrap().take().is_some());
+  w.set_position(0);
+  {
+    let mut compr = CompR::new(&mut w, &mut f).with_drop_policy(DropPolicy::Stash(slot.clone()));
+    compr.read(&mut [0]).unwrap();
+  }
+  assert!(slot.lock().unwrap().take().is_some());
+  {
+    let state = CompR::new(&mut w, &mut f).with_drop_policy(DropPolicy::Stash(slot.clone()))
+      .suspend().unwrap();
+    let mut compr = CompR::resume(&mut w, state);
+    compr.read(&mut [0]).unwrap();
+  }
+  assert!(slot.lock().unwrap().take().is_some());
+  // composers with stash policy could be used from another thread
+  let slot2 = slot.clone();
+  thread::spawn(move || {
+    let mut w : Cuvec = Cursor::new(Vec::new());
+    let mut f = FailEnd;
+    let mut compw = CompW::new(&mut w, &mut f).with_drop_policy(DropPolicy::Stash(slot2));
+    check_send(&compw);
+    compw.write(&[0]).unwrap();
+  }).join().unwrap();
+  assert!(slot.lock().unwrap().take().is_some());
+}
+
+#[test]
+#[should_panic]
+#[cfg(debug_assertions)]
+fn test_drop_policy_panic() {
+  let mut w : Cuvec = Cursor::new(Vec::new());
+  let mut f = FailEnd;
+  let mut compw = CompW::new(&mut w, &mut f).with_drop_policy(DropPolicy::PanicDebug);
+  compw.write(&[0]).unwrap();
+}
+
+/// writer counting inner flush
+pub struct FlushCount(Cuvec, usize);
+
+impl Write for FlushCount {
+  fn write(&mut self, cont : &[u8]) -> Result<usize> {self.0.write(cont)}
+  fn flush(&mut self) -> Result<()> {self.1 += 1; Ok(())}
+}
+
+#[test]
+fn test_flush_all() {
+  let mut w = FlushCount(Cursor::new(Vec::new()), 0);
+  let mut ew = CompExtW(EndStream::new(2), Ciph::new(1,2));
+  {
+    let mut compw = CompW::new(&mut w, &mut ew);
+    compw.write(&[1,2,3]).unwrap();
+    compw.flush().unwrap();
+  }
+  // drop does not flush inner
+  assert!(w.1 == 1);
+  let mut mw = MultiWExt::new(vec![EndStream::new(2), EndStream::new(3)]);
+  {
+    let mut compw = new_multiw(&mut w, &mut mw);
+    compw.write(&[1,2,3]).unwrap();
+    compw.flush().unwrap();
+    compw.finish().unwrap();
+  }
+  // finish does not flush inner either
+  assert!(w.1 == 2);
+  // non recursive
+  mw.flush_into(&mut w).unwrap();
+  assert!(w.1 == 2);
+  mw.flush_all_into(&mut w).unwrap();
+  assert!(w.1 == 3);
+}
+
+#[test]
+fn test_auto_read_end() {
+  let mut w : Cuvec = Cursor::new(Vec::new());
+  let mut e = EndStream::new(2);
+  // window size multiple (endstream padding is read as content)
+  for m in [&[1,2,3,4][..], &[5,6][..]].iter() {
+    let mut compw = CompW::new(&mut w, &mut e);
+    compw.write_all(m).unwrap();
+    compw.write_end().unwrap();
+  }
+  w.set_position(0);
+  let mut e = EndStream::new(2);
+  let mut compr = CompR::new(&mut w, &mut e).with_auto_read_end(true);
+  let mut res = Vec::new();
+  compr.read_to_end(&mut res).unwrap();
+  assert!(&res[..] == &[1,2,3,4][..]);
+  // auto mode kept by suspend and resume
+  let state = compr.suspend().unwrap();
+  let mut compr = CompR::resume(&mut w, state);
+  assert!(compr.read(&mut []).unwrap() == 0);
+  res.clear();
+  compr.read_to_end(&mut res).unwrap();
+  assert!(&res[..] == &[5,6][..]);
+}
+
+/// layer with a one byte header (content is not changed)
+pub struct OneByteHeader;
+
+impl ExtRead for OneByteHeader {
+  #[inline]
+  fn read_header<R : Read>(&mut self, r : &mut R) -> Result<()> {
+    let mut b = [0];
+    r.read_exact(&mut b)
+  }
+  #[inline]
+  fn read_from<R : Read>(&mut self, r : &mut R, buf : &mut[u8]) -> Result<usize> {
+    r.read(buf)
+  }
+  #[inline]
+  fn read_end<R : Read>(&mut self, _ : &mut R) -> Result<()> {Ok(())}
+}
+
+#[test]
+fn test_auto_read_end_eof() {
+  let mut h = OneByteHeader;
+  let mut c : Cuvec = Cursor::new(vec![0,1,2]);
+  {
+    let mut compr = CompR::new(&mut c, &mut h).with_auto_read_end(true);
+    let mut res = Vec::new();
+    compr.read_to_end(&mut res).unwrap();
+    assert!(&res[..] == &[1,2][..]);
+    // end of stream before header
+    assert!(compr.read(&mut [0]).unwrap() == 0);
+    assert!(compr.read(&mut [0]).unwrap() == 0);
+  }
+  // without auto read end, missing header is an error
+  c.set_position(3);
+  assert!(CompR::new(&mut c, &mut h).read(&mut [0]).is_err());
+  let mut r = CompROwn::new(Cursor::new(vec![0]), OneByteHeader).with_auto_read_end(true);
+  assert!(r.read(&mut [0]).unwrap() == 0);
+  let (c, state) = r.suspend().unwrap();
+  let mut r = CompROwn::resume(c, state);
+  assert!(r.read(&mut [0]).unwrap() == 0);
+}
+
+#[test]
+fn test_message() {
+  let mut mw = MessageWriter::new(Cursor::new(Vec::new()), EndStream::new(2));
+  mw.write_message(&[1,2,3,4]).unwrap();
+  mw.start_message().unwrap();
+  mw.write_all(&[5,6]).unwrap();
+  mw.write_all(&[7,8]).unwrap();
+  mw.finish_message().unwrap();
+  mw.write_message(&[9,10]).unwrap();
+  mw.write_message(&[11,12]).unwrap();
+  let (mut c, _) = mw.into_inner();
+  c.set_position(0);
+
+  let mut mr = MessageReader::new(c, EndStream::new(2));
+  {
+    let mut m = mr.next_message().unwrap().unwrap();
+    let mut res = Vec::new();
+    m.read_to_end(&mut res).unwrap();
+    assert!(&res[..] == &[1,2,3,4][..]);
+    assert!(m.read(&mut [0]).unwrap() == 0);
+  }
+  {
+    // partially read : skipped on drop
+    let mut m = mr.next_message().unwrap().unwrap();
+    let mut buf = [0;1];
+    m.read_exact(&mut buf).unwrap();
+    assert!(buf[0] == 5);
+  }
+  assert!(mr.read_message(1).is_err());
+  assert!(&mr.read_message(2).unwrap().unwrap()[..] == &[11,12][..]);
+  assert!(mr.next_message().unwrap().is_none());
+}
+
+/// reader ending content at once, counting (failing) read end
+pub struct CountFailEnd(usize);
+
+impl ExtRead for CountFailEnd {
+  #[inline]
+  fn read_header<R : Read>(&mut self, _ : &mut R) -> Result<()> {Ok(())}
+  #[inline]
+  fn read_from<R : Read>(&mut self, _ : &mut R, _ : &mut[u8]) -> Result<usize> {Ok(0)}
+  #[inline]
+  fn read_end<R : Read>(&mut self, _ : &mut R) -> Result<()> {
+    self.0 += 1;
+    Err(Error::new(ErrorKind::Other, "read end failure"))
+  }
+}
+
+#[test]
+fn test_message_drop() {
+  let mut mr = MessageReader::new(Cursor::new(vec![0]), CountFailEnd(0));
+  {
+    let _m = mr.next_message().unwrap().unwrap();
+  }
+  // read end failed on drop, it is not called again by inner CompR drop
+  assert!((mr.1).0 == 1);
+  {
+    let m = mr.next_message().unwrap().unwrap();
+    assert!(m.finish().is_err());
+  }
+  assert!((mr.1).0 == 2);
+}
+
+/// owned composer built and returned from a function
+fn owned_w () -> CompWOwn<Cuvec, CompExtW<EndStream, Ciph>> {
+  CompWOwn::new(Cursor::new(Vec::new()), CompExtW(EndStream::new(2), Ciph::new(1,2)))
+}
+
+#[test]
+fn test_owned() {
+  let mut w = owned_w();
+  w.write(&[1,2,3]).unwrap();
+  let (mut c, state) = w.suspend().unwrap();
+  c.write(&[123]).unwrap();
+  let mut w = CompWOwn::resume(c, state);
+  w.write(&[4,5]).unwrap();
+  let (mut c, _) = w.finish().unwrap();
+  c.set_position(0);
+
+  let mut buf = vec![0;5];
+  let mut r = CompROwn::new(c, CompExtR(EndStream::new(2), Ciph::new(1,2)));
+  let mut l = 0;
+  while l < 3 {
+    l += r.read(&mut buf[l..3]).unwrap();
+  }
+  let (mut c, state) = r.suspend().unwrap();
+  c.read(&mut buf[3..4]).unwrap();
+  assert!(buf[3] == 123);
+  let mut r = CompROwn::resume(c, state);
+  while l < 5 {
+    l += r.read(&mut buf[l..5]).unwrap();
+  }
+  r.finish().unwrap();
+  assert!(&buf[..] == &[1,2,3,4,5][..]);
+}
+
 #[test]
 fn test_ciph() {
   let towrite_size = 123;
@@ -516,3 +840,460 @@ fn test_ciph_end_mult () {
 
 
 
+
+#[test]
+fn test_skip () {
+  let mut w = Cursor::new(Vec::new());
+  let first : Vec<u8> = (0..100).collect();
+  let second : Vec<u8> = (100..130).collect();
+  let mut c = CompExtW(EndStream::new(15),Ciph::new(3,7));
+  for m in [&first[..], &second[..]].iter() {
+    c.write_header(&mut w).unwrap();
+    c.write_all_into(&mut w, m).unwrap();
+    c.write_end(&mut w).unwrap();
+  }
+  let (ciphs,ciphsr) = inst_ciph_end_mult ();
+  let mut mciphsext = MultiWExt::new(ciphs);
+  for m in [&first[..], &second[..]].iter() {
+    mciphsext.write_header(&mut w).unwrap();
+    mciphsext.write_all_into(&mut w, m).unwrap();
+    mciphsext.write_end(&mut w).unwrap();
+  }
+
+  w.set_position(0);
+  let mut cr = CompExtR(EndStream::new(15),Ciph::new(3,7));
+  let mut cr2 = CompExtR(EndStream::new(15),Ciph::new(3,7));
+  {
+    let mut chain = cr.chain(&mut cr2);
+    chain.read_header(&mut w).unwrap();
+    assert!(chain.skip_from(&mut w, 0).unwrap() == 0);
+    // endstream padding is read as content
+    assert!(chain.discard_to_end(&mut w).unwrap() >= (first.len() + second.len()) as u64);
+    chain.read_end(&mut w).unwrap();
+  }
+
+  let mut buf = vec![0;5];
+  let mut mciphsext = MultiRExt::new(ciphsr);
+  mciphsext.read_header(&mut w).unwrap();
+  let s = mciphsext.skip_from(&mut w, 10).unwrap();
+  assert!(s > 0 && s <= 10);
+  mciphsext.read_exact_from(&mut w, &mut buf[..]).unwrap();
+  assert!(&buf[..] == &first[s..s + 5]);
+  assert!(mciphsext.discard_to_end(&mut w).unwrap() >= (first.len() - s - 5) as u64);
+  mciphsext.read_end(&mut w).unwrap();
+  let mut buf = vec![0;second.len()];
+  mciphsext.read_header(&mut w).unwrap();
+  mciphsext.read_exact_from(&mut w, &mut buf[..]).unwrap();
+  assert!(buf == second);
+  mciphsext.read_end(&mut w).unwrap();
+}
+
+/// writer accepting `.1` bytes (one byte per call), then failing with WouldBlock
+struct BlockAfter(Vec<u8>, usize);
+
+impl Write for BlockAfter {
+  fn write(&mut self, cont : &[u8]) -> Result<usize> {
+    if self.1 == 0 {
+      return Err(Error::new(ErrorKind::WouldBlock, "no more room"));
+    }
+    if cont.is_empty() {
+      return Ok(0);
+    }
+    self.1 -= 1;
+    self.0.push(cont[0]);
+    Ok(1)
+  }
+  fn flush(&mut self) -> Result<()> {Ok(())}
+}
+
+/// writer counting write calls, `.2` is max bytes per call
+struct Calls(Vec<u8>, usize, usize);
+
+impl Write for Calls {
+  fn write(&mut self, cont : &[u8]) -> Result<usize> {
+    self.write_vectored(&[IoSlice::new(cont)])
+  }
+  fn write_vectored(&mut self, bufs : &[IoSlice]) -> Result<usize> {
+    self.1 += 1;
+    let mut tot = 0;
+    for b in bufs.iter() {
+      let l = ::std::cmp::min(b.len(), self.2 - tot);
+      self.0.extend_from_slice(&b[..l]);
+      tot += l;
+    }
+    Ok(tot)
+  }
+  fn flush(&mut self) -> Result<()> {Ok(())}
+}
+
+#[test]
+fn test_vectored () {
+  // header and content in a single call
+  let mut w = Calls(Vec::new(), 0, 100);
+  let mut hpos = 0;
+  assert!(write_with_header(&mut w, &[1,2], &mut hpos, &[3,4,5]).unwrap() == 3);
+  assert!(w.0 == vec![1,2,3,4,5] && w.1 == 1 && hpos == 2);
+  // partial write of header is completed without content
+  let mut w = Calls(Vec::new(), 0, 1);
+  let mut hpos = 0;
+  assert!(write_with_header(&mut w, &[1,2], &mut hpos, &[3,4,5]).unwrap() == 0);
+  assert!(w.0 == vec![1,2] && hpos == 2);
+  // header progress is kept on error
+  let mut w = BlockAfter(Vec::new(), 1);
+  let mut hpos = 0;
+  assert!(write_with_header(&mut w, &[1,2,3], &mut hpos, &[4,5]).unwrap_err().kind() == ErrorKind::WouldBlock);
+  assert!(w.0 == vec![1] && hpos == 1);
+  w.1 = 100;
+  assert!(write_with_header(&mut w, &[1,2,3], &mut hpos, &[4,5]).unwrap() == 0);
+  assert!(w.0 == vec![1,2,3] && hpos == 3);
+
+  // forwarded to inner writer
+  let mut w = Calls(Vec::new(), 0, 100);
+  let mut id = ID();
+  {
+    let mut cw = CompW::new(&mut w, &mut id);
+    assert!(cw.write_vectored(&[IoSlice::new(&[1,2]), IoSlice::new(&[]), IoSlice::new(&[3])]).unwrap() == 3);
+  }
+  assert!(w.0 == vec![1,2,3] && w.1 == 1);
+
+  // default implementation
+  let content : Vec<u8> = (0..100).collect();
+  let mut w = Cursor::new(Vec::new());
+  let mut c = CompExtW(EndStream::new(15),Ciph::new(3,7));
+  {
+    let mut cw = CompW::new(&mut w, &mut c);
+    let n = cw.write_vectored(&[IoSlice::new(&[]), IoSlice::new(&content[..])]).unwrap();
+    assert!(n > 0);
+    cw.write_all(&content[n..]).unwrap();
+  }
+  w.set_position(0);
+  let mut buf = vec![0;content.len()];
+  let mut cr = CompExtR(EndStream::new(15),Ciph::new(3,7));
+  let mut cr = CompR::new(&mut w, &mut cr);
+  let mut i = 0;
+  while i < buf.len() {
+    let (b1, b2) = buf.split_at_mut(i + 1);
+    let n = cr.read_vectored(&mut [IoSliceMut::new(&mut []), IoSliceMut::new(&mut b1[i..]), IoSliceMut::new(b2)]).unwrap();
+    assert!(n > 0);
+    i += n;
+  }
+  assert!(buf == content);
+}
+
+#[test]
+fn test_dyn () {
+  let first : Vec<u8> = (0..100).collect();
+  let second : Vec<u8> = (100..130).collect();
+  let mut w = Cursor::new(Vec::new());
+  // blocking endstream as internal layer
+  let mut mw : DynMultiWExt = MultiWExt::new(vec![
+    Box::new(EndStream::new(5)) as Box<dyn DynExtWrite>,
+    Box::new(Void),
+    Box::new(CompExtW(EndStream::new(3),Ciph::new(2,5))),
+  ]);
+  for m in [&first[..], &second[..]].iter() {
+    mw.write_header(&mut w).unwrap();
+    mw.write_all_into(&mut w, m).unwrap();
+    mw.write_end(&mut w).unwrap();
+  }
+
+  w.set_position(0);
+  let mut mr : DynMultiRExt = MultiRExt::new(vec![
+    Box::new(EndStream::new(5)) as Box<dyn DynExtRead>,
+    Box::new(Void),
+    Box::new(CompExtR(EndStream::new(3),Ciph::new(2,5))),
+  ]);
+  mr.read_header(&mut w).unwrap();
+  let mut buf = vec![0;first.len()];
+  mr.read_exact_from(&mut w, &mut buf[..]).unwrap();
+  assert!(buf == first);
+  mr.read_end(&mut w).unwrap();
+  mr.read_header(&mut w).unwrap();
+  assert!(mr.discard_to_end(&mut w).unwrap() >= second.len() as u64);
+  mr.read_end(&mut w).unwrap();
+  assert!(w.position() == w.get_ref().len() as u64);
+
+  // single layer as trait object
+  let mut c : Box<dyn DynExtWrite> = Box::new(Ciph::new(2,5));
+  let mut w = Vec::new();
+  c.dyn_write_header(&mut w).unwrap();
+  c.dyn_write_all_into(&mut w, &first[..]).unwrap();
+  c.dyn_write_end(&mut w).unwrap();
+  let mut input = &w[..];
+  let r : &mut dyn Read = &mut input;
+  let mut c : Box<dyn DynExtRead> = Box::new(Ciph::new(2,5));
+  c.dyn_read_header(r).unwrap();
+  c.dyn_read_exact_from(r, &mut buf[..]).unwrap();
+  assert!(buf == first);
+  c.dyn_read_end(r).unwrap();
+}
+
+#[test]
+fn test_layers () {
+  let m : Vec<u8> = (0..40).collect();
+  let mut w = Cursor::new(Vec::new());
+  let mut mw : DynMultiWExt = MultiWExt::new(Vec::new());
+  let mut mr : DynMultiRExt = MultiRExt::new(Vec::new());
+  // no layer
+  mw.write_header(&mut w).unwrap();
+  mw.write_all_into(&mut w, &m[..]).unwrap();
+  mw.write_end(&mut w).unwrap();
+  assert!(&w.get_ref()[..] == &m[..]);
+  mw.push_layer(Box::new(EndStream::new(5))).unwrap();
+  mw.write_header(&mut w).unwrap();
+  mw.write_all_into(&mut w, &m[..]).unwrap();
+  // mid message
+  assert!(mw.push_layer(Box::new(Void)).is_err());
+  assert!(mw.pop_layer().is_err());
+  mw.write_end(&mut w).unwrap();
+  mw.push_layer(Box::new(Ciph::new(2,5))).unwrap();
+  mw.write_header(&mut w).unwrap();
+  mw.write_all_into(&mut w, &m[..]).unwrap();
+  mw.write_end(&mut w).unwrap();
+  assert!(mw.pop_layer().unwrap().is_some());
+  mw.replace_layer(0, Box::new(EndStream::new(3))).unwrap();
+  assert!(mw.replace_layer(1, Box::new(Void)).err().unwrap().kind() == ErrorKind::InvalidInput);
+  mw.write_header(&mut w).unwrap();
+  mw.write_all_into(&mut w, &m[..]).unwrap();
+  mw.write_end(&mut w).unwrap();
+
+  w.set_position(0);
+  let mut buf = vec![0;m.len()];
+  mr.read_exact_from(&mut w, &mut buf[..]).unwrap();
+  assert!(buf == m);
+  mr.push_layer(Box::new(EndStream::new(5))).unwrap();
+  mr.read_header(&mut w).unwrap();
+  mr.read_exact_from(&mut w, &mut buf[..]).unwrap();
+  assert!(mr.replace_layer(0, Box::new(Void)).is_err());
+  assert!(buf == m);
+  mr.read_end(&mut w).unwrap();
+  mr.push_layer(Box::new(Ciph::new(2,5))).unwrap();
+  mr.read_header(&mut w).unwrap();
+  mr.read_exact_from(&mut w, &mut buf[..]).unwrap();
+  assert!(buf == m);
+  mr.read_end(&mut w).unwrap();
+  assert!(mr.pop_layer().unwrap().is_some());
+  mr.replace_layer(0, Box::new(EndStream::new(3))).unwrap();
+  mr.read_header(&mut w).unwrap();
+  mr.read_exact_from(&mut w, &mut buf[..]).unwrap();
+  assert!(buf == m);
+  mr.read_end(&mut w).unwrap();
+  assert!(w.position() == w.get_ref().len() as u64);
+}
+
+#[test]
+fn test_stack () {
+  let m : Vec<u8> = (0..100).collect();
+  let (mut sw, mut sr) = ext_stack!(EndStream::new(5), Void, Ciph::new(2,5), EndStream::new(3));
+  {
+    let _ : &ext_stack_w_type!(EndStream, Void, Ciph, EndStream) = &sw;
+    let _ : &ext_stack_r_type!(EndStream, Void, Ciph, EndStream) = &sr;
+  }
+  // same as nested composition
+  let mut nw = CompExtW(EndStream::new(5), CompExtW(Void, CompExtW(Ciph::new(2,5), EndStream::new(3))));
+  let mut w = Vec::new();
+  let mut w2 = Vec::new();
+  sw.write_header(&mut w).unwrap();
+  sw.write_all_into(&mut w, &m[..]).unwrap();
+  sw.write_end(&mut w).unwrap();
+  nw.write_header(&mut w2).unwrap();
+  nw.write_all_into(&mut w2, &m[..]).unwrap();
+  nw.write_end(&mut w2).unwrap();
+  assert!(w == w2);
+  let mut r = &w[..];
+  let mut buf = vec![0;m.len()];
+  sr.read_header(&mut r).unwrap();
+  sr.read_exact_from(&mut r, &mut buf[..]).unwrap();
+  assert!(buf == m);
+  sr.read_end(&mut r).unwrap();
+  assert!(r.is_empty());
+
+  // distinct writer and reader
+  let (mut sw, mut sr) = ext_stack!(Ciph::new(2,5) => Ciph::new(2,5), Void => ID());
+  let mut w = Vec::new();
+  sw.write_header(&mut w).unwrap();
+  sw.write_all_into(&mut w, &m[..]).unwrap();
+  sw.write_end(&mut w).unwrap();
+  let mut r = &w[..];
+  sr.read_header(&mut r).unwrap();
+  sr.read_exact_from(&mut r, &mut buf[..]).unwrap();
+  assert!(buf == m);
+  sr.read_end(&mut r).unwrap();
+  let single = ext_stack_w!(Void,);
+  let _ : &Void = &single;
+}
+
+#[test]
+fn test_duplex () {
+  let m : Vec<u8> = (0..100).collect();
+  let mut d = Duplex::from_fn(|| (EndStream::new(5), Ciph::new(2,5)));
+  let mut w = Vec::new();
+  d.write_header(&mut w).unwrap();
+  d.write_all_into(&mut w, &m[..]).unwrap();
+  d.write_end(&mut w).unwrap();
+  // same order as CompExtW
+  let mut w2 = Vec::new();
+  let mut cw = CompExtW(EndStream::new(5), Ciph::new(2,5));
+  cw.write_header(&mut w2).unwrap();
+  cw.write_all_into(&mut w2, &m[..]).unwrap();
+  cw.write_end(&mut w2).unwrap();
+  assert!(w == w2);
+  let mut buf = vec![0;m.len()];
+  let mut r = &w[..];
+  d.read_header(&mut r).unwrap();
+  d.read_exact_from(&mut r, &mut buf[..]).unwrap();
+  assert!(buf == m);
+  d.read_end(&mut r).unwrap();
+  assert!(r.is_empty());
+
+  // read while a write is in progress
+  let mut d = Duplex::from_fn(|| (EndStream::new(5), Ciph::new(2,5)));
+  let mut w3 = Vec::new();
+  d.write_header(&mut w3).unwrap();
+  d.write_all_into(&mut w3, &m[..7]).unwrap();
+  let mut r = &w[..];
+  d.read_header(&mut r).unwrap();
+  d.read_exact_from(&mut r, &mut buf[..]).unwrap();
+  assert!(buf == m);
+  d.read_end(&mut r).unwrap();
+  d.write_all_into(&mut w3, &m[7..]).unwrap();
+  d.write_end(&mut w3).unwrap();
+  assert!(w3 == w);
+
+  let (mut dw, mut dr) = Duplex::new((Ciph::new(2,5), EndStream::new(3)), (Ciph::new(2,5), EndStream::new(3))).split();
+  let mut w = Vec::new();
+  dw.write_header(&mut w).unwrap();
+  dw.write_all_into(&mut w, &m[..]).unwrap();
+  dw.write_end(&mut w).unwrap();
+  let mut r = &w[..];
+  dr.read_header(&mut r).unwrap();
+  dr.read_exact_from(&mut r, &mut buf[..]).unwrap();
+  assert!(buf == m);
+  dr.read_end(&mut r).unwrap();
+}
+
+/// write then read `m`
+fn write_read<EW : ExtWrite, ER : ExtRead>(ew : &mut EW, er : &mut ER, m : &[u8]) {
+  let mut w = Vec::new();
+  ew.write_header(&mut w).unwrap();
+  ew.write_all_into(&mut w, m).unwrap();
+  ew.write_end(&mut w).unwrap();
+  let mut buf = vec![0;m.len()];
+  let mut r = &w[..];
+  er.read_header(&mut r).unwrap();
+  er.read_exact_from(&mut r, &mut buf[..]).unwrap();
+  assert!(&buf[..] == m);
+  er.read_end(&mut r).unwrap();
+  assert!(r.is_empty());
+}
+
+#[test]
+fn test_comp_ext_split () {
+  let m : Vec<u8> = (0..100).collect();
+  let (mut cw, mut cr) = CompExtW(EndStream::new(5), Ciph::new(2,5)).split();
+  let (mut cw2, mut cr2) = CompExtR(EndStream::new(5), Ciph::new(2,5)).split();
+  write_read(&mut cw, &mut cr, &m[..]);
+  write_read(&mut cw, &mut cr2, &m[..]);
+  write_read(&mut cw2, &mut cr, &m[..]);
+}
+
+#[test]
+fn test_chain () {
+  let m1 : Vec<u8> = (0..40).collect();
+  let m2 : Vec<u8> = (40..100).collect();
+  let mut e = EndStream::new(5);
+  let mut c = Ciph::new(2,5);
+  let mut w = Vec::new();
+  {
+    let mut chain = e.chain_write(&mut c);
+    chain.write_header(&mut w).unwrap();
+    chain.write_all_into(&mut w, &m1[..]).unwrap();
+    assert!(chain.in_first());
+    chain.switch_to_second(&mut w).unwrap();
+    assert!(chain.in_second());
+    chain.write_all_into(&mut w, &m2[..]).unwrap();
+    chain.write_end(&mut w).unwrap();
+    assert!(chain.in_first());
+  }
+  // same as writing each layer
+  let mut w2 = Vec::new();
+  e.write_header(&mut w2).unwrap();
+  e.write_all_into(&mut w2, &m1[..]).unwrap();
+  e.write_end(&mut w2).unwrap();
+  c.write_header(&mut w2).unwrap();
+  c.write_all_into(&mut w2, &m2[..]).unwrap();
+  c.write_end(&mut w2).unwrap();
+  assert!(w == w2);
+  // second header already written
+  {
+    c.write_header(&mut w).unwrap();
+    let mut chain = e.chain_write_with_initialized(&mut c);
+    chain.write_header(&mut w).unwrap();
+    chain.write_all_into(&mut w, &m1[..]).unwrap();
+    chain.write_end(&mut w).unwrap();
+  }
+
+  let mut e = EndStream::new(5);
+  let mut c = Ciph::new(2,5);
+  let mut r = &w[..];
+  let mut buf = vec![0;m1.len() + m2.len()];
+  {
+    let mut chain = e.chain(&mut c);
+    chain.read_header(&mut r).unwrap();
+    // no endstream padding (full windows)
+    chain.read_exact_from(&mut r, &mut buf[..]).unwrap();
+    assert!(chain.in_second());
+    assert!(&buf[..m1.len()] == &m1[..]);
+    assert!(&buf[m1.len()..] == &m2[..]);
+    chain.read_end(&mut r).unwrap();
+  }
+  {
+    c.read_header(&mut r).unwrap();
+    let mut chain = e.chain_with_initialized(&mut c);
+    chain.read_header(&mut r).unwrap();
+    chain.read_exact_from(&mut r, &mut buf[..m1.len()]).unwrap();
+    assert!(&buf[..m1.len()] == &m1[..]);
+    chain.read_end(&mut r).unwrap();
+  }
+  assert!(r.is_empty());
+
+  // failed header of second is written again, end of first is not
+  let mut e = EndStream::new(5);
+  let mut f = FailHeaderOnce(false);
+  let mut w = Vec::new();
+  {
+    let mut chain = e.chain_write(&mut f);
+    chain.write_header(&mut w).unwrap();
+    chain.write_all_into(&mut w, &m1[..3]).unwrap();
+    assert!(chain.switch_to_second(&mut w).unwrap_err().kind() == ErrorKind::WouldBlock);
+    assert!(chain.in_first());
+    chain.switch_to_second(&mut w).unwrap();
+    assert!(chain.in_second());
+    chain.write_all_into(&mut w, &m2[..3]).unwrap();
+    chain.write_end(&mut w).unwrap();
+  }
+  let mut w2 = Vec::new();
+  e.write_header(&mut w2).unwrap();
+  e.write_all_into(&mut w2, &m1[..3]).unwrap();
+  e.write_end(&mut w2).unwrap();
+  w2.push(9);
+  w2.extend_from_slice(&m2[..3]);
+  assert!(w == w2);
+}
+
+/// writes 9 as header, first header write fails with WouldBlock
+struct FailHeaderOnce(bool);
+
+impl ExtWrite for FailHeaderOnce {
+  fn write_header<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    if !self.0 {
+      self.0 = true;
+      return Err(Error::new(ErrorKind::WouldBlock, "first header"));
+    }
+    w.write_all(&[9])
+  }
+  fn write_into<W : Write>(&mut self, w : &mut W, cont : &[u8]) -> Result<usize> {
+    w.write(cont)
+  }
+  fn write_end<W : Write>(&mut self, _ : &mut W) -> Result<()> {Ok(())}
+}
@@ -0,0 +1,67 @@
+//! Transport checks of composition with transport aware test layers (from `readwrite-comp-test`,
+//! it could not be use from unit tests as it links its own build of this crate).
+
+extern crate readwrite_comp;
+extern crate readwrite_comp_test;
+
+use std::io::ErrorKind;
+use readwrite_comp::{
+  ExtRead,
+  CompExtW,
+  CompExtR,
+  MultiWExt,
+  MultiRExt,
+};
+use readwrite_comp_test::check_transports;
+use readwrite_comp_test::layers::{
+  EndStream,
+  Ciph,
+};
+
+#[test]
+fn endstream_read_end_truncated_test() {
+  let mut er = EndStream::new(2);
+  // window with next window marker, then end of stream
+  let mut input = &[1,2,1,3][..];
+  er.read_header(&mut input).unwrap();
+  assert!(er.read_end(&mut input).unwrap_err().kind() == ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn endstream_skip_test() {
+  let mut er = EndStream::new(2);
+  // content [1,2,3,4,5] with padding and terminal 0, followed by a byte of next message
+  let mut input = &[1,2,1,3,4,1,5,2,0,9][..];
+  er.read_header(&mut input).unwrap();
+  // skip stops at window end
+  assert!(er.skip_from(&mut input, 3).unwrap() == 2);
+  assert!(er.discard_to_end(&mut input).unwrap() == 4);
+  er.read_end(&mut input).unwrap();
+  assert!(input == &[9][..]);
+}
+
+#[test]
+fn endstream_transports_test() {
+  check_transports(|| EndStream::new(4), || EndStream::new(4), true).unwrap();
+}
+
+#[test]
+fn ciph_transports_test() {
+  // Ciph does not end its content, it needs an inner end stream
+  check_transports(|| CompExtW(EndStream::new(4), Ciph::new(3,5)),
+    || CompExtR(EndStream::new(4), Ciph::new(0,5)), true).unwrap();
+}
+
+#[test]
+fn multi_transports_test() {
+  check_transports(|| MultiWExt::new(vec![EndStream::new(2), EndStream::new(7)]),
+    || MultiRExt::new(vec![EndStream::new(2), EndStream::new(7)]), true).unwrap();
+  check_transports(|| MultiWExt::new(vec![
+      CompExtW(EndStream::new(4), Ciph::new(3,5)),
+      CompExtW(EndStream::new(3), Ciph::new(1,2)),
+    ]),
+    || MultiRExt::new(vec![
+      CompExtR(EndStream::new(4), Ciph::new(0,5)),
+      CompExtR(EndStream::new(3), Ciph::new(0,2)),
+    ]), true).unwrap();
+}
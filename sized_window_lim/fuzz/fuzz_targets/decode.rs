@@ -0,0 +1,62 @@
+//! Decode arbitrary input : SizedWindows read must return an error or a result, never panic or
+//! loop.
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate readwrite_comp;
+extern crate sized_windows_lim;
+
+use std::io::Cursor;
+use readwrite_comp::ExtRead;
+use sized_windows_lim::{
+  SizedWindowsParams,
+  SizedWindows,
+};
+
+struct Grow;
+struct Written;
+
+impl SizedWindowsParams for Grow {
+  const INIT_SIZE : usize = 4;
+  const MAX_SIZE : usize = 64;
+  const GROWTH_RATIO : Option<(usize,usize)> = Some((3,2));
+  const WRITE_SIZE : bool = false;
+  const SECURE_PAD : bool = false;
+}
+
+impl SizedWindowsParams for Written {
+  const INIT_SIZE : usize = 4;
+  const MAX_SIZE : usize = 64;
+  const GROWTH_RATIO : Option<(usize,usize)> = None;
+  const WRITE_SIZE : bool = true;
+  const SECURE_PAD : bool = false;
+}
+
+fn decode<ER : ExtRead>(data : &[u8], er : &mut ER) {
+  let mut input = Cursor::new(data);
+  let mut buf = [0;32];
+  // at most one message per input byte
+  for _ in 0..data.len() + 1 {
+    if er.read_header(&mut input).is_err() {
+      return;
+    }
+    loop {
+      match er.read_from(&mut input, &mut buf) {
+        Ok(0) => break,
+        Ok(_) => (),
+        Err(_) => return,
+      }
+    }
+    if er.read_end(&mut input).is_err() {
+      return;
+    }
+    if input.position() as usize == data.len() {
+      return;
+    }
+  }
+}
+
+fuzz_target!(|data: &[u8]| {
+  decode(data, &mut SizedWindows::new(Grow));
+  decode(data, &mut SizedWindows::new(Written));
+});
@@ -1,8 +1,27 @@
 extern crate rand;
 extern crate readwrite_comp;
 extern crate byteorder;
-use rand::OsRng;
-use rand::Rng;
+#[cfg(feature="async")]
+extern crate futures_io;
+#[cfg(feature="serde")]
+extern crate serde;
+#[cfg(feature="serde")]
+use serde::{
+  Serialize,
+  Deserialize,
+};
+use rand::{
+  RngCore,
+  CryptoRng,
+  SeedableRng,
+};
+use rand::rngs::{
+  StdRng,
+  OsRng,
+};
+use std::fmt;
+use std::error::Error;
+use std::io;
 use std::io::{
   Write,
   Read,
@@ -13,16 +32,43 @@ use std::io::{
 use readwrite_comp::{
   ExtRead,
   ExtWrite,
+  write_with_header,
 };
 
 
-use std::marker::PhantomData;
+#[cfg(feature="async")]
+use readwrite_comp::{
+  AsyncExtRead,
+  AsyncExtWrite,
+  PollBuf,
+};
+#[cfg(feature="async")]
+use futures_io::{
+  AsyncRead,
+  AsyncWrite,
+};
+#[cfg(feature="async")]
+use std::pin::Pin;
+#[cfg(feature="async")]
+use std::task::{
+  Context,
+  Poll,
+};
 use byteorder::{
+  ByteOrder,
   LittleEndian,
-  ReadBytesExt,
-  WriteBytesExt,
 };
 
+/// return on pending or error, similar to `try!` for `Poll<Result<T>>`
+#[cfg(feature="async")]
+macro_rules! try_ready {($e:expr) => (
+  match $e {
+    Poll::Ready(Ok(t)) => t,
+    Poll::Ready(Err(e)) => return Poll::Ready(Err(From::from(e))),
+    Poll::Pending => return Poll::Pending,
+  }
+)}
+
 /// conf trait
 pub trait SizedWindowsParams {
   const INIT_SIZE : usize;
@@ -35,147 +81,503 @@ pub trait SizedWindowsParams {
   const SECURE_PAD : bool = true;
 }
 
-#[derive(Clone)]
-pub struct SizedWindows<P : SizedWindowsParams>  {
+/// Invalid window size or params
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum WindowError {
+  /// window size read is 0
+  ZeroSize,
+  /// window size read is bigger than max size
+  TooBig { size : u64, max : usize },
+  /// params are not usable
+  InvalidParams(&'static str),
+}
+
+impl fmt::Display for WindowError {
+  fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      WindowError::ZeroSize => write!(f, "window size of 0"),
+      WindowError::TooBig { size, max } => write!(f, "window size {} bigger than max size {}", size, max),
+      WindowError::InvalidParams(m) => write!(f, "invalid windows params : {}", m),
+    }
+  }
+}
+
+impl Error for WindowError {}
+
+/// `InvalidData` error (`InvalidInput` for params), the `WindowError` is available through
+/// `get_ref`.
+impl From<WindowError> for IoError {
+  fn from(e : WindowError) -> IoError {
+    let kind = match e {
+      WindowError::InvalidParams(_) => IoErrorKind::InvalidInput,
+      _ => IoErrorKind::InvalidData,
+    };
+    IoError::new(kind, e)
+  }
+}
+
+/// params access used by `SizedWindows`, implemented for every `SizedWindowsParams` (constant
+/// params, zero sized) and for `SizedWindowsConfig` (runtime params).
+pub trait WindowsParams {
+  fn init_size(&self) -> usize;
+  fn max_size(&self) -> usize;
+  fn growth_ratio(&self) -> Option<(usize,usize)>;
+  fn write_size(&self) -> bool;
+  fn secure_pad(&self) -> bool;
+
+  /// init size must be between 1 and max size, and growth ratio at least 1.
+  fn check(&self) -> ::std::result::Result<(), WindowError> {
+    if self.init_size() == 0 {
+      return Err(WindowError::InvalidParams("init size is 0"));
+    }
+    if self.init_size() > self.max_size() {
+      return Err(WindowError::InvalidParams("init size bigger than max size"));
+    }
+    if let Some((n,d)) = self.growth_ratio() {
+      if d == 0 || n < d {
+        return Err(WindowError::InvalidParams("growth ratio lower than 1"));
+      }
+    }
+    Ok(())
+  }
+}
+
+impl<P : SizedWindowsParams> WindowsParams for P {
+  #[inline]
+  fn init_size(&self) -> usize {
+    P::INIT_SIZE
+  }
+  #[inline]
+  fn max_size(&self) -> usize {
+    P::MAX_SIZE
+  }
+  #[inline]
+  fn growth_ratio(&self) -> Option<(usize,usize)> {
+    P::GROWTH_RATIO
+  }
+  #[inline]
+  fn write_size(&self) -> bool {
+    P::WRITE_SIZE
+  }
+  #[inline]
+  fn secure_pad(&self) -> bool {
+    P::SECURE_PAD
+  }
+}
+
+/// runtime params, same meaning as `SizedWindowsParams` consts (same wire format for same
+/// values).
+#[derive(Clone,Debug,PartialEq,Eq)]
+#[cfg_attr(feature="serde", derive(Serialize,Deserialize))]
+pub struct SizedWindowsConfig {
+  pub init_size : usize,
+  pub max_size : usize,
+  pub growth_ratio : Option<(usize,usize)>,
+  pub write_size : bool,
+  #[cfg_attr(feature="serde", serde(default = "default_secure_pad"))]
+  pub secure_pad : bool,
+}
+
+#[cfg(feature="serde")]
+fn default_secure_pad() -> bool {
+  true
+}
+
+impl SizedWindowsConfig {
+  /// config with same values as constant params
+  pub fn from_params<P : SizedWindowsParams>() -> Self {
+    SizedWindowsConfig {
+      init_size : P::INIT_SIZE,
+      max_size : P::MAX_SIZE,
+      growth_ratio : P::GROWTH_RATIO,
+      write_size : P::WRITE_SIZE,
+      secure_pad : P::SECURE_PAD,
+    }
+  }
+}
+
+impl WindowsParams for SizedWindowsConfig {
+  #[inline]
+  fn init_size(&self) -> usize {
+    self.init_size
+  }
+  #[inline]
+  fn max_size(&self) -> usize {
+    self.max_size
+  }
+  #[inline]
+  fn growth_ratio(&self) -> Option<(usize,usize)> {
+    self.growth_ratio
+  }
+  #[inline]
+  fn write_size(&self) -> bool {
+    self.write_size
+  }
+  #[inline]
+  fn secure_pad(&self) -> bool {
+    self.secure_pad
+  }
+}
+
+/// `RNG` is used for padding (if secure pad), seeded once (a clone uses a new rng seeded from
+/// os, not a copy). If seeding from os fails, padding write returns an error.
+pub struct SizedWindows<P : WindowsParams, RNG : RngCore + CryptoRng = StdRng>  {
   init_size : usize, // TODO rename to last_size
   winrem : usize,
   resizable : bool,
-  _p : PhantomData<P>,
+  /// sync only : size of first window is written with first content
+  hpending : bool,
+  /// sync only : control bytes of current window (size of first window, or marker and size of
+  /// next window), `whpos` of the `whlen` bytes are written
+  whead : [u8;9],
+  whlen : usize,
+  whpos : usize,
+  /// sync only : partially read window size (`rhlen` bytes), and if the byte after a window was
+  /// read with next window size still to read
+  rhead : [u8;8],
+  rhlen : usize,
+  rnext : bool,
+  /// async only : pending control bytes (write) or partially read control bytes (read)
+  #[cfg(feature="async")]
+  apending : PollBuf,
+  #[cfg(feature="async")]
+  astep : AsyncStep,
+  params : P,
+  /// None if it could not be seeded from os
+  rng : Option<RNG>,
 }
 
-impl<P : SizedWindowsParams> SizedWindows<P> {
-  /// p param is only to simplify type inference (it is an empty struct)
-  pub fn new (_ : P) -> Self {
+/// async only : next step, as async read of control bytes is done lazily (window end
+/// byte could be pending after content read).
+#[cfg(feature="async")]
+#[derive(Clone)]
+enum AsyncStep {
+  Content,
+  /// read byte after window
+  Marker,
+  /// read window size
+  Size,
+  /// write end padding done, terminal 0 pending
+  End,
+}
+
+/// padding of a clone must differ from padding of the original
+impl<P : WindowsParams + Clone, RNG : RngCore + CryptoRng + SeedableRng> Clone for SizedWindows<P, RNG> {
+  fn clone(&self) -> Self {
     SizedWindows {
-      init_size : P::INIT_SIZE,
-      winrem : P::INIT_SIZE,
-      resizable : P::GROWTH_RATIO.is_some(),
-      _p : PhantomData,
+      init_size : self.init_size,
+      winrem : self.winrem,
+      resizable : self.resizable,
+      hpending : self.hpending,
+      whead : self.whead,
+      whlen : self.whlen,
+      whpos : self.whpos,
+      rhead : self.rhead,
+      rhlen : self.rhlen,
+      rnext : self.rnext,
+      #[cfg(feature="async")]
+      apending : self.apending.clone(),
+      #[cfg(feature="async")]
+      astep : self.astep.clone(),
+      params : self.params.clone(),
+      rng : RNG::from_rng(OsRng).ok(),
+    }
+  }
+}
+
+impl<P : WindowsParams> SizedWindows<P, StdRng> {
+  /// p is either an empty struct implementing `SizedWindowsParams` or a `SizedWindowsConfig`,
+  /// padding rng is seeded from os (on failure, padding write returns an error).
+  /// Params are not validated (only a debug assertion), `try_new` should be used for params
+  /// which may be invalid (for instance a deserialized `SizedWindowsConfig`).
+  pub fn new (p : P) -> Self {
+    Self::with_opt_rng(p, StdRng::from_rng(OsRng).ok())
+  }
+
+  /// error if params are invalid (see `WindowsParams::check`)
+  pub fn try_new (p : P) -> ::std::result::Result<Self, WindowError> {
+    try!(p.check());
+    Ok(Self::new(p))
+  }
+
+  /// deterministic padding (for reproducible tests)
+  pub fn seeded (p : P, seed : u64) -> Self {
+    Self::with_rng(p, StdRng::seed_from_u64(seed))
+  }
+}
+
+impl<P : WindowsParams, RNG : RngCore + CryptoRng> SizedWindows<P, RNG> {
+  /// params are not validated (see `new`).
+  pub fn with_rng (p : P, rng : RNG) -> Self {
+    Self::with_opt_rng(p, Some(rng))
+  }
+
+  fn with_opt_rng (p : P, rng : Option<RNG>) -> Self {
+    debug_assert!(p.check().is_ok(), "invalid windows params");
+    SizedWindows {
+      init_size : p.init_size(),
+      winrem : p.init_size(),
+      resizable : p.growth_ratio().is_some(),
+      hpending : false,
+      whead : [0;9],
+      whlen : 0,
+      whpos : 0,
+      rhead : [0;8],
+      rhlen : 0,
+      rnext : false,
+      #[cfg(feature="async")]
+      apending : PollBuf::new(),
+      #[cfg(feature="async")]
+      astep : AsyncStep::Content,
+      params : p,
+      rng,
     }
   }
+  #[inline]
+  pub fn params(&self) -> &P {
+    &self.params
+  }
+
   #[inline]
   fn next_winsize<R : Read> (&mut self, r : &mut R ) -> Result<()>{
     // winrem for next
-    self.winrem = if P::WRITE_SIZE {
-      try!(r.read_u64::<LittleEndian>()) as usize
+    self.winrem = if self.params.write_size() {
+      try!(self.read_winsize(r))
     } else {
-      if self.resizable {
-        match P::GROWTH_RATIO {
-          Some((n,d)) => {
-            let n_size = self.init_size * n / d;
-            if n_size < P::MAX_SIZE {
-              n_size
-            } else {
-              self.resizable = false;
-              P::MAX_SIZE
-            }
-          },
-          None => P::INIT_SIZE,
-        }
-      } else {
-        self.init_size
-      }
+      self.grow_winsize()
     };
     self.init_size = self.winrem;
     Ok(())
 
   }
-}
 
-impl<P : SizedWindowsParams> ExtWrite for SizedWindows<P> {
+  /// read window size, progress is kept on error
+  fn read_winsize<R : Read>(&mut self, r : &mut R) -> Result<usize> {
+    while self.rhlen < 8 {
+      match r.read(&mut self.rhead[self.rhlen..]) {
+        Ok(0) => return Err(IoError::new(IoErrorKind::UnexpectedEof, "End of stream in window size")),
+        Ok(rr) => self.rhlen += rr,
+        Err(ref e) if e.kind() == IoErrorKind::Interrupted => (),
+        Err(e) => return Err(e),
+      }
+    }
+    self.rhlen = 0;
+    self.valid_winsize(LittleEndian::read_u64(&self.rhead))
+  }
+
+  /// check window size read from stream
+  fn valid_winsize(&self, size : u64) -> Result<usize> {
+    if size == 0 {
+      return Err(WindowError::ZeroSize.into());
+    }
+    if size > self.params.max_size() as u64 {
+      return Err(WindowError::TooBig { size, max : self.params.max_size() }.into());
+    }
+    Ok(size as usize)
+  }
+
+  /// size of next window when it is not written
   #[inline]
-  fn write_header<W : Write>(&mut self, w : &mut W) -> Result<()> {
-    if P::WRITE_SIZE {
-      try!(w.write_u64::<LittleEndian>(self.winrem as u64));
+  fn grow_winsize(&mut self) -> usize {
+    if self.resizable {
+      match self.params.growth_ratio() {
+        Some((n,d)) => {
+          // max size on overflow (and on invalid ratio)
+          match self.init_size.checked_mul(n).and_then(|s| s.checked_div(d)) {
+            Some(n_size) if n_size > 0 && n_size < self.params.max_size() => n_size,
+            _ => {
+              self.resizable = false;
+              self.params.max_size()
+            },
+          }
+        },
+        None => self.params.init_size(),
+      }
+    } else {
+      self.init_size
     }
+  }
+}
+
+impl<P : WindowsParams, RNG : RngCore + CryptoRng> ExtWrite for SizedWindows<P, RNG> {
+  #[inline]
+  fn write_header<W : Write>(&mut self, _ : &mut W) -> Result<()> {
+    // written with first content (single write)
+    self.hpending = self.params.write_size();
 //    self.init_size = self.winrem;
     Ok(())
   }
 
+  /// window control bytes are written in the same `write_vectored` call as window content.
+  /// Window state is updated when control bytes are queued, on error their remaining part is
+  /// written by next call (or by flush or write end).
   fn write_into<W : Write>(&mut self, w : &mut W, cont : &[u8]) -> Result<usize> {
     let mut tot = 0;
     while tot < cont.len() {
-      if self.winrem == 0 {
-
-        // init next winrem
-        self.winrem = if self.resizable {
-          match P::GROWTH_RATIO {
-            Some((n,d)) => {
-              let n_size = self.init_size * n / d;
-              if n_size < P::MAX_SIZE {
-                n_size
-              } else {
-                self.resizable = false;
-                P::MAX_SIZE
-              }
-            },
-            None => P::INIT_SIZE,
-          }
-        } else {
-          self.init_size
-        };
-
-        self.init_size = self.winrem;
-        // non 0 (terminal) value
-        try!(w.write(&[1]));
-        if P::WRITE_SIZE {
-          try!(w.write_u64::<LittleEndian>(self.winrem as u64));
-        };
+      if self.whpos == self.whlen && (self.winrem == 0 || self.hpending) {
+        let mut hl = 0;
+        if self.winrem == 0 {
+          self.winrem = self.grow_winsize();
+          self.init_size = self.winrem;
+          // non 0 (terminal) value
+          self.whead[0] = 1;
+          hl = 1;
+        }
+        if self.params.write_size() {
+          LittleEndian::write_u64(&mut self.whead[hl..hl + 8], self.winrem as u64);
+          hl += 8;
+        }
+        self.whlen = hl;
+        self.whpos = 0;
+        self.hpending = false;
       }
 
-
-
-      let ww = if self.winrem + tot < cont.len() {
-        try!(w.write(&cont[tot..tot + self.winrem]))
+      let l = if self.winrem + tot < cont.len() { self.winrem } else { cont.len() - tot };
+      let head = self.whpos < self.whlen;
+      let r = if head {
+        write_with_header(w, &self.whead[..self.whlen], &mut self.whpos, &cont[tot..tot + l])
       } else {
-        try!(w.write(&cont[tot..]))
+        w.write(&cont[tot..tot + l])
       };
-      tot += ww;
-      self.winrem -= ww;
+      match r {
+        // control bytes completed without content
+        Ok(0) if head => (),
+        Ok(0) => return if tot > 0 { Ok(tot) } else {
+          Err(IoError::new(IoErrorKind::WriteZero, "failed to write window content"))
+        },
+        Ok(ww) => {
+          tot += ww;
+          self.winrem -= ww;
+        },
+        Err(ref e) if e.kind() == IoErrorKind::Interrupted => (),
+        Err(e) => return if tot > 0 { Ok(tot) } else { Err(e) },
+      }
     }
     Ok(tot)
   }
 
+  /// only write size of first window if no content was written
+  #[inline]
+  fn flush_into<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    self.write_pending_header(w)
+  }
+
+  /// padding is written until winrem is 0, so on error write_end could be call again.
   #[inline]
   fn write_end<W : Write>(&mut self, r : &mut W) -> Result<()> {
-    // TODO buffer is not nice here and more over we need random content (nice for debugging
-    // without but in tunnel it gives tunnel length) -> !!!
+    try!(self.write_pending_header(r));
     let mut buffer = [0; 256];
-
-    if P::SECURE_PAD {
-      let mut rng = try!(OsRng::new()); // TODO test for perf (if cache)
-      rng.fill_bytes(&mut buffer);
-    };
     while self.winrem != 0 {
-      let ww = if self.winrem > 256 {
-        try!(r.write(&mut buffer))
-      } else {
-        try!(r.write(&mut buffer[..self.winrem]))
-      };
-      self.winrem -= ww;
+      let l = if self.winrem > 256 { 256 } else { self.winrem };
+      if self.params.secure_pad() {
+        try!(self.pad_rng()).fill_bytes(&mut buffer[..l]);
+      }
+      match r.write(&buffer[..l]) {
+        Ok(0) => return Err(IoError::new(IoErrorKind::WriteZero, "failed to write padding")),
+        Ok(ww) => self.winrem -= ww,
+        Err(ref e) if e.kind() == IoErrorKind::Interrupted => (),
+        Err(e) => return Err(e),
+      }
     }
     // terminal 0
-    try!(r.write(&[0]));
+    try!(r.write_all(&[0]));
     // init as new
-    self.init_size = P::INIT_SIZE;
-    self.winrem = P::INIT_SIZE;
+    self.init_size = self.params.init_size();
+    self.winrem = self.params.init_size();
     Ok(())
   }
 
 }
 
+impl<P : WindowsParams, RNG : RngCore + CryptoRng> SizedWindows<P, RNG> {
+
+  /// padding rng, error if it could not be seeded from os
+  #[allow(clippy::io_other_error)]
+  fn pad_rng(&mut self) -> Result<&mut RNG> {
+    self.rng.as_mut().ok_or_else(|| IoError::new(IoErrorKind::Other, "padding rng could not be seeded from os"))
+  }
 
-impl<P : SizedWindowsParams> ExtRead for SizedWindows<P> {
+  /// write size of first window if no content was written, or remaining control bytes of a
+  /// failed write (progress is kept on error)
+  fn write_pending_header<W : Write>(&mut self, w : &mut W) -> Result<()> {
+    if self.hpending {
+      LittleEndian::write_u64(&mut self.whead[..8], self.winrem as u64);
+      self.whlen = 8;
+      self.whpos = 0;
+      self.hpending = false;
+    }
+    while self.whpos < self.whlen {
+      match w.write(&self.whead[self.whpos..self.whlen]) {
+        Ok(0) => return Err(IoError::new(IoErrorKind::WriteZero, "failed to write window size")),
+        Ok(ww) => self.whpos += ww,
+        Err(ref e) if e.kind() == IoErrorKind::Interrupted => (),
+        Err(e) => return Err(e),
+      }
+    }
+    Ok(())
+  }
+
+  /// read byte after window, return true if terminal 0
+  fn read_marker<R : Read>(&mut self, r : &mut R) -> Result<bool> {
+    let mut b = [0];
+    loop {
+      match r.read(&mut b) {
+        Ok(0) => return Err(IoError::new(IoErrorKind::UnexpectedEof,
+          "No bytes after window size, do not know if ended or repeat")),
+        Ok(_) => return Ok(b[0] == 0),
+        Err(ref e) if e.kind() == IoErrorKind::Interrupted => (),
+        Err(e) => return Err(e),
+      }
+    }
+  }
+
+  /// at window end, read byte after window (and next window size if not ended)
+  fn check_window<R : Read>(&mut self, r : &mut R) -> Result<()> {
+    if self.init_size != 0 && self.winrem == 0 {
+      // byte after window is read lazily (no content lost on error)
+      if !self.rnext {
+        if try!(self.read_marker(r)) {
+          // ended (case where there is no padding or we do not know what we read and read also
+          // the padding)
+          self.init_size = 0;
+          return Ok(());
+        }
+        self.rnext = true;
+      }
+      // new window and drop this byte
+      try!(self.next_winsize(r));
+      self.rnext = false;
+    }
+    Ok(())
+  }
+
+  /// drop up to `n` bytes of current window, return the number of bytes dropped
+  fn skip_window<R : Read>(&mut self, r : &mut R, n : usize) -> Result<usize> {
+    let l = if self.winrem < n { self.winrem } else { n };
+    let mut t = r.by_ref().take(l as u64);
+    let res = io::copy(&mut t, &mut io::sink());
+    let ww = l - t.limit() as usize;
+    self.winrem -= ww;
+    try!(res);
+    if ww == 0 && l > 0 {
+      return Err(IoError::new(IoErrorKind::UnexpectedEof, "End of stream in window"));
+    }
+    Ok(ww)
+  }
+}
+
+impl<P : WindowsParams, RNG : RngCore + CryptoRng> ExtRead for SizedWindows<P, RNG> {
   #[inline]
   fn read_header<R : Read>(&mut self, r : &mut R) -> Result<()> {
-    if P::WRITE_SIZE {
-      self.winrem = try!(r.read_u64::<LittleEndian>()) as usize;
+    if self.params.write_size() {
+      self.winrem = try!(self.read_winsize(r));
       self.init_size = self.winrem;
     }
     Ok(())
   }
 
   fn read_from<R : Read>(&mut self, r : &mut R, buf : &mut[u8]) -> Result<usize> {
+    try!(self.check_window(r));
     if self.init_size == 0 {
       // ended read (still padded)
       return Ok(0);
@@ -186,83 +588,275 @@ impl<P : SizedWindowsParams> ExtRead for SizedWindows<P> {
       try!(r.read(buf))
     };
     self.winrem -= rr;
-    if self.winrem == 0 {
-      let mut b = [0];
-      let rb = try!(r.read(&mut b));
-      if rb != 1 {
-        return
-         Err(IoError::new(IoErrorKind::Other, "No bytes after window size, do not know if ended or repeat"));
-      }
-      if b[0] == 0 {
-        // ended (case where there is no padding or we do not know what we read and read also
-        // the padding)
-        self.init_size = 0;
-      } else {
-        // new window and drop this byte
-        try!(self.next_winsize(r));
-      }
-    }
     Ok(rr)
   }
+
+  /// window content is dropped without copy in caller buffer, a call skips at most the end of
+  /// current window.
+  fn skip_from<R : Read>(&mut self, r : &mut R, n : usize) -> Result<usize> {
+    try!(self.check_window(r));
+    if self.init_size == 0 {
+      return Ok(0);
+    }
+    self.skip_window(r, n)
+  }
+
   #[inline]
   fn read_end<R : Read>(&mut self, r : &mut R) -> Result<()> {
 
-    if self.winrem == 0 {
-      self.init_size = P::INIT_SIZE;
-      self.winrem = P::INIT_SIZE;
-      Ok(())
-    } else {
-    // TODO buffer is needed here -> see if Read interface should not have a fn drop where we read
-      // without buffer and drop content. For now hardcoded buffer length...
-      let mut buffer = [0; 256];
-      buffer[0] = 1;
-      while buffer[0] != 0 {
+    while self.init_size != 0 {
+      while self.winrem != 0 {
+        let rem = self.winrem;
+        try!(self.skip_window(r, rem));
+      }
+      try!(self.check_window(r));
+    }
+    // init as new
+    self.init_size = self.params.init_size();
+    self.winrem = self.params.init_size();
+    Ok(())
+  }
+
+}
+
+#[cfg(feature="async")]
+impl<P : WindowsParams, RNG : RngCore + CryptoRng> AsyncExtWrite for SizedWindows<P, RNG> {
+  fn poll_write_header<W : AsyncWrite + Unpin>(&mut self, cx : &mut Context, w : &mut W) -> Poll<Result<()>> {
+    if self.params.write_size() && self.apending.is_empty() {
+      let mut b = [0;8];
+      LittleEndian::write_u64(&mut b, self.winrem as u64);
+      self.apending.push(&b[..]);
+    }
+    self.apending.poll_drain(cx, w)
+  }
+
+  fn poll_write_into<W : AsyncWrite + Unpin>(&mut self, cx : &mut Context, w : &mut W, cont : &[u8]) -> Poll<Result<usize>> {
+    let mut tot = 0;
+    loop {
+      // control bytes of a new window
+      match self.apending.poll_drain(cx, w) {
+        Poll::Ready(Ok(())) => (),
+        Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+        Poll::Pending => return if tot > 0 { Poll::Ready(Ok(tot)) } else { Poll::Pending },
+      }
+      if tot == cont.len() {
+        return Poll::Ready(Ok(tot));
+      }
+      if self.winrem == 0 {
+        self.winrem = self.grow_winsize();
+        self.init_size = self.winrem;
+        // non 0 (terminal) value
+        self.apending.push(&[1]);
+        if self.params.write_size() {
+          let mut b = [0;8];
+          LittleEndian::write_u64(&mut b, self.winrem as u64);
+          self.apending.push(&b[..]);
+        }
+        continue;
+      }
+      let l = if self.winrem + tot < cont.len() { self.winrem } else { cont.len() - tot };
+      match Pin::new(&mut *w).poll_write(cx, &cont[tot..tot + l]) {
+        Poll::Ready(Ok(0)) => return if tot > 0 { Poll::Ready(Ok(tot)) } else {
+          Poll::Ready(Err(IoError::new(IoErrorKind::WriteZero, "failed to write window content")))
+        },
+        Poll::Ready(Ok(ww)) => {
+          tot += ww;
+          self.winrem -= ww;
+        },
+        Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+        Poll::Pending => return if tot > 0 { Poll::Ready(Ok(tot)) } else { Poll::Pending },
+      }
+    }
+  }
 
+  fn poll_write_end<W : AsyncWrite + Unpin>(&mut self, cx : &mut Context, w : &mut W) -> Poll<Result<()>> {
+    if let AsyncStep::Content = self.astep {
+      try_ready!(self.apending.poll_drain(cx, w));
+      if self.winrem != 0 {
+        let mut buffer = [0; 256];
         while self.winrem != 0 {
-          let ww = if self.winrem > 256 {
-            try!(r.read(&mut buffer))
-          } else {
-            try!(r.read(&mut buffer[..self.winrem]))
-          };
-          if ww ==  0 {
-//           error!("read end pading : missing {}",self.winrem);
-           return
-             Err(IoError::new(IoErrorKind::Other, "End read missing padding"));
+          let l = if self.winrem > 256 { 256 } else { self.winrem };
+          if self.params.secure_pad() {
+            match self.pad_rng() {
+              Ok(rng) => rng.fill_bytes(&mut buffer[..l]),
+              Err(e) => return Poll::Ready(Err(e)),
+            }
+          }
+          let ww = try_ready!(Pin::new(&mut *w).poll_write(cx, &buffer[..l]));
+          if ww == 0 {
+            return Poll::Ready(Err(IoError::new(IoErrorKind::WriteZero, "failed to write padding")));
           }
           self.winrem -= ww;
         }
-
-        let ww = try!(r.read(&mut buffer[..1]));
-        if ww != 1  {
- //          error!("read end no terminal 0 : nbread {}\n{}\n",ww,buffer[0]);
-           return
-             Err(IoError::new(IoErrorKind::Other, "End read does not find expected terminal 0 of windows"));
-        }
-        if buffer[0] != 0 {
-          try!(self.next_winsize(r));
-        }
       }
-      // init as new
-      self.init_size = P::INIT_SIZE;
-      self.winrem = P::INIT_SIZE;
+      // terminal 0
+      self.apending.push(&[0]);
+      self.astep = AsyncStep::End;
+    }
+    try_ready!(self.apending.poll_drain(cx, w));
+    // init as new
+    self.astep = AsyncStep::Content;
+    self.init_size = self.params.init_size();
+    self.winrem = self.params.init_size();
+    Poll::Ready(Ok(()))
+  }
+}
 
-      Ok(())
+#[cfg(feature="async")]
+impl<P : WindowsParams, RNG : RngCore + CryptoRng> SizedWindows<P, RNG> {
+  /// read pending control bytes (after window content), return true if terminal 0 was read
+  fn poll_read_control<R : AsyncRead + Unpin>(&mut self, cx : &mut Context, r : &mut R) -> Poll<Result<bool>> {
+    loop {
+      match self.astep {
+        AsyncStep::Marker => {
+          try_ready!(self.apending.poll_fill(cx, r, 1));
+          let b = self.apending.content()[0];
+          self.apending.clear();
+          if b == 0 {
+            self.astep = AsyncStep::Content;
+            return Poll::Ready(Ok(true));
+          } else if self.params.write_size() {
+            self.astep = AsyncStep::Size;
+          } else {
+            self.winrem = self.grow_winsize();
+            self.init_size = self.winrem;
+            self.astep = AsyncStep::Content;
+          }
+        },
+        AsyncStep::Size => {
+          try_ready!(self.apending.poll_fill(cx, r, 8));
+          let size = LittleEndian::read_u64(self.apending.content());
+          self.apending.clear();
+          self.winrem = match self.valid_winsize(size) {
+            Ok(s) => s,
+            Err(e) => return Poll::Ready(Err(e)),
+          };
+          self.init_size = self.winrem;
+          self.astep = AsyncStep::Content;
+        },
+        AsyncStep::Content | AsyncStep::End => return Poll::Ready(Ok(false)),
+      }
     }
   }
+}
 
+#[cfg(feature="async")]
+impl<P : WindowsParams, RNG : RngCore + CryptoRng> AsyncExtRead for SizedWindows<P, RNG> {
+  fn poll_read_header<R : AsyncRead + Unpin>(&mut self, cx : &mut Context, r : &mut R) -> Poll<Result<()>> {
+    if self.params.write_size() {
+      try_ready!(self.apending.poll_fill(cx, r, 8));
+      let size = LittleEndian::read_u64(self.apending.content());
+      self.apending.clear();
+      self.winrem = match self.valid_winsize(size) {
+        Ok(s) => s,
+        Err(e) => return Poll::Ready(Err(e)),
+      };
+      self.init_size = self.winrem;
+    }
+    self.astep = AsyncStep::Content;
+    Poll::Ready(Ok(()))
+  }
 
+  fn poll_read_from<R : AsyncRead + Unpin>(&mut self, cx : &mut Context, r : &mut R, buf : &mut[u8]) -> Poll<Result<usize>> {
+    if try_ready!(self.poll_read_control(cx, r)) {
+      self.init_size = 0;
+    }
+    if self.init_size == 0 {
+      // ended read (still padded)
+      return Poll::Ready(Ok(0));
+    }
+    let l = if self.winrem < buf.len() { self.winrem } else { buf.len() };
+    let rr = try_ready!(Pin::new(&mut *r).poll_read(cx, &mut buf[..l]));
+    self.winrem -= rr;
+    if self.winrem == 0 {
+      self.astep = AsyncStep::Marker;
+    }
+    Poll::Ready(Ok(rr))
+  }
 
+  #[allow(clippy::io_other_error)]
+  fn poll_read_end<R : AsyncRead + Unpin>(&mut self, cx : &mut Context, r : &mut R) -> Poll<Result<()>> {
+    loop {
+      if try_ready!(self.poll_read_control(cx, r)) {
+        break;
+      }
+      if self.init_size == 0 {
+        // terminal already read
+        break;
+      }
+      if self.winrem == 0 {
+        self.astep = AsyncStep::Marker;
+        continue;
+      }
+      let mut buffer = [0; 256];
+      let l = if self.winrem > 256 { 256 } else { self.winrem };
+      let rr = try_ready!(Pin::new(&mut *r).poll_read(cx, &mut buffer[..l]));
+      if rr == 0 {
+        return Poll::Ready(Err(IoError::new(IoErrorKind::Other, "End read missing padding")));
+      }
+      self.winrem -= rr;
+    }
+    // init as new
+    self.astep = AsyncStep::Content;
+    self.init_size = self.params.init_size();
+    self.winrem = self.params.init_size();
+    Poll::Ready(Ok(()))
+  }
 }
 
 #[cfg(test)]
 mod test {
 
   extern crate readwrite_comp_test;
-  use self::readwrite_comp_test::test_bytes_wr;
+  use self::readwrite_comp_test::{
+    test_bytes_wr,
+    check_transports,
+    check_ext,
+  };
+  use self::readwrite_comp_test::transport::{
+    CallWrite,
+    FaultWrite,
+    ShortWrite,
+    retry_blocking,
+  };
+  use readwrite_comp::{
+    MessageWriter,
+    MessageReader,
+    CompW,
+    CompR,
+  };
+  use std::io::Cursor;
+  use readwrite_comp::{
+    MultiWExt,
+    MultiRExt,
+  };
+  #[cfg(feature="async")]
+  use self::readwrite_comp_test::async_test::{
+    test_bytes_wr_async,
+    test_bytes_w_sync_r_async,
+    test_bytes_w_async_r_sync,
+  };
   use super::{
     SizedWindowsParams,
+    SizedWindowsConfig,
     SizedWindows,
+    WindowError,
+  };
+  use readwrite_comp::ExtRead;
+  use readwrite_comp::ExtWrite;
+  use std::io::{
+    Read,
+    Write,
+    Result,
+    ErrorKind,
+  };
+  use rand::{
+    Rng,
+    SeedableRng,
   };
+  use rand::rngs::StdRng;
+  #[cfg(feature="serde")]
+  extern crate serde_json;
   struct Params1;
   struct Params2;
   struct Params3;
@@ -347,6 +941,415 @@ mod test {
     ).unwrap();
   }
 
+  #[cfg(feature="async")]
+  #[test]
+  fn windows_async_test () {
+    let mut et = SizedWindows::new(Params1);
+    let mut et2 = SizedWindows::new(Params1);
+    test_bytes_wr_async(
+      150,
+      7,
+      &mut et,
+      &mut et2,
+    ).unwrap();
+    let mut et = SizedWindows::new(Params2);
+    let mut et2 = SizedWindows::new(Params2);
+    test_bytes_wr_async(
+      150,
+      36,
+      &mut et,
+      &mut et2,
+    ).unwrap();
+    let mut et = SizedWindows::new(Params3);
+    let mut et2 = SizedWindows::new(Params3);
+    test_bytes_wr_async(
+      150,
+      200,
+      &mut et,
+      &mut et2,
+    ).unwrap();
+    let mut et = SizedWindows::new(Params4);
+    let mut et2 = SizedWindows::new(Params4);
+    test_bytes_wr_async(
+      150,
+      7,
+      &mut et,
+      &mut et2,
+    ).unwrap();
+  }
 
-}
+  #[cfg(feature="async")]
+  #[test]
+  fn windows_async_sync_test () {
+    let mut et = SizedWindows::new(Params1);
+    let mut et2 = SizedWindows::new(Params1);
+    test_bytes_w_sync_r_async(
+      150,
+      7,
+      &mut et,
+      &mut et2,
+    ).unwrap();
+    test_bytes_w_async_r_sync(
+      150,
+      7,
+      &mut et,
+      &mut et2,
+    ).unwrap();
+    let mut et = SizedWindows::new(Params3);
+    let mut et2 = SizedWindows::new(Params3);
+    test_bytes_w_sync_r_async(
+      150,
+      15,
+      &mut et,
+      &mut et2,
+    ).unwrap();
+    test_bytes_w_async_r_sync(
+      150,
+      15,
+      &mut et,
+      &mut et2,
+    ).unwrap();
+  }
+
+  #[test]
+  fn windows_ext_test () {
+    check_ext(|| SizedWindows::new(Params1), || SizedWindows::new(Params1), true).unwrap();
+    check_ext(|| SizedWindows::new(Params2), || SizedWindows::new(Params2), true).unwrap();
+    check_ext(|| SizedWindows::new(Params3), || SizedWindows::new(Params3), true).unwrap();
+    check_ext(|| SizedWindows::new(Params4), || SizedWindows::new(Params4), true).unwrap();
+    check_ext(
+      || MultiWExt::new(vec![SizedWindows::new(Params1), SizedWindows::new(Params1)]),
+      || MultiRExt::new(vec![SizedWindows::new(Params1), SizedWindows::new(Params1)]),
+      true).unwrap();
+  }
+
+  #[test]
+  fn windows_config_test () {
+    // same wire format as constant params
+    let c1 = SizedWindowsConfig::from_params::<Params1>();
+    let c3 = SizedWindowsConfig::from_params::<Params3>();
+    test_bytes_wr(150, 7, &mut SizedWindows::new(Params1), &mut SizedWindows::new(c1.clone())).unwrap();
+    test_bytes_wr(150, 7, &mut SizedWindows::new(c1.clone()), &mut SizedWindows::new(Params1)).unwrap();
+    test_bytes_wr(150, 15, &mut SizedWindows::new(c3.clone()), &mut SizedWindows::new(Params3)).unwrap();
+    check_transports(|| SizedWindows::new(c1.clone()), || SizedWindows::new(Params1), true).unwrap();
+    check_transports(|| SizedWindows::new(Params3), || SizedWindows::new(c3.clone()), true).unwrap();
+  }
+
+  #[cfg(feature="serde")]
+  #[test]
+  fn windows_config_serde_test () {
+    let c : SizedWindowsConfig = serde_json::from_str(
+      r#"{ "init_size" : 20, "max_size" : 2048, "growth_ratio" : [4, 3], "write_size" : true }"#).unwrap();
+    assert!(c == SizedWindowsConfig::from_params::<Params3>());
+    let c2 = serde_json::from_str(&serde_json::to_string(&c).unwrap()).unwrap();
+    assert!(c == c2);
+  }
 
+  #[test]
+  fn windows_rng_test () {
+    use readwrite_comp::ExtWrite;
+    let conf = SizedWindowsConfig {
+      init_size : 1000,
+      max_size : 1000,
+      growth_ratio : None,
+      write_size : false,
+      secure_pad : true,
+    };
+    let pad = |mut sw : SizedWindows<SizedWindowsConfig>| {
+      let mut w = Vec::new();
+      sw.write_header(&mut w).unwrap();
+      sw.write_end(&mut w).unwrap();
+      w
+    };
+    // reproducible
+    let p1 = pad(SizedWindows::seeded(conf.clone(), 1));
+    assert!(p1 == pad(SizedWindows::seeded(conf.clone(), 1)));
+    assert!(p1 != pad(SizedWindows::seeded(conf.clone(), 2)));
+    // no repeated chunk
+    assert!(p1.len() == 1001);
+    assert!(p1[..256] != p1[256..512]);
+    test_bytes_wr(150, 7, &mut SizedWindows::seeded(Params1, 3), &mut SizedWindows::new(Params1)).unwrap();
+    // clone does not repeat padding
+    let sw = SizedWindows::seeded(conf.clone(), 1);
+    assert!(pad(sw.clone()) != p1);
+    assert!(pad(sw) == p1);
+  }
+
+  fn window_error(r : Result<()>) -> WindowError {
+    let e = r.unwrap_err();
+    assert!(e.kind() == ErrorKind::InvalidData);
+    e.get_ref().and_then(|e| e.downcast_ref::<WindowError>()).unwrap().clone()
+  }
+
+  /// read all messages, panic if a read does not progress
+  fn decode<R : Read, ER : ExtRead>(input : &mut R, len : usize, er : &mut ER) -> Result<()> {
+    let mut buf = [0;32];
+    let mut nb_read = 0;
+    loop {
+      try!(er.read_header(input));
+      loop {
+        nb_read += 1;
+        assert!(nb_read <= 2 * len + 2, "reader loops");
+        if try!(er.read_from(input, &mut buf)) == 0 {
+          break;
+        }
+      }
+      try!(er.read_end(input));
+    }
+  }
+
+  #[test]
+  fn unseeded_pad_test () {
+    let conf = SizedWindowsConfig {
+      init_size : 20,
+      max_size : 2048,
+      growth_ratio : None,
+      write_size : true,
+      secure_pad : true,
+    };
+    // as if seeding from os failed
+    let mut sw : SizedWindows<_, StdRng> = SizedWindows::with_opt_rng(conf, None);
+    let mut w = Vec::new();
+    sw.write_header(&mut w).unwrap();
+    sw.write_all_into(&mut w, &[1,2,3]).unwrap();
+    assert!(sw.write_end(&mut w).unwrap_err().kind() == ErrorKind::Other);
+  }
+
+  #[test]
+  fn windows_validation_test () {
+    let mut sw = SizedWindows::new(Params4);
+    assert!(window_error(sw.read_header(&mut &[0;8][..])) == WindowError::ZeroSize);
+    assert!(window_error(sw.read_header(&mut &[0xFF;8][..])) == WindowError::TooBig { size : u64::MAX, max : 2048 });
+    // second window too big
+    let mut input = vec![20, 0, 0, 0, 0, 0, 0, 0];
+    input.extend_from_slice(&[3;20]);
+    input.extend_from_slice(&[1, 0, 0x10, 0, 0, 0, 0, 0, 0]);
+    assert!(window_error(decode(&mut &input[..], input.len(), &mut SizedWindows::new(Params4)))
+      == WindowError::TooBig { size : 0x1000, max : 2048 });
+    // params
+    let conf = SizedWindowsConfig {
+      init_size : 0,
+      max_size : 2048,
+      growth_ratio : None,
+      write_size : true,
+      secure_pad : true,
+    };
+    assert!(SizedWindows::try_new(conf.clone()).is_err());
+    assert!(SizedWindows::try_new(SizedWindowsConfig { init_size : 4096, .. conf.clone() }).is_err());
+    assert!(SizedWindows::try_new(SizedWindowsConfig { init_size : 20, growth_ratio : Some((1,0)), .. conf.clone() }).is_err());
+    assert!(SizedWindows::try_new(SizedWindowsConfig { init_size : 20, .. conf.clone() }).is_ok());
+    // growth overflow
+    let mut sw = SizedWindows::new(SizedWindowsConfig {
+      init_size : usize::MAX / 2,
+      max_size : usize::MAX,
+      growth_ratio : Some((4,3)),
+      .. conf.clone()
+    });
+    assert!(sw.grow_winsize() == usize::MAX);
+  }
+
+  #[test]
+  #[should_panic]
+  #[cfg(debug_assertions)]
+  fn windows_new_invalid_test () {
+    SizedWindows::new(SizedWindowsConfig {
+      init_size : 0,
+      max_size : 2048,
+      growth_ratio : None,
+      write_size : true,
+      secure_pad : true,
+    });
+  }
+
+  #[test]
+  fn windows_malicious_test () {
+    let conf = SizedWindowsConfig {
+      init_size : 4,
+      max_size : 64,
+      growth_ratio : Some((3,2)),
+      write_size : false,
+      secure_pad : false,
+    };
+    let mut rng = StdRng::seed_from_u64(0);
+    for i in 0..5000 {
+      let len = rng.gen_range(0..200);
+      let mut input = vec![0;len];
+      rng.fill(&mut input[..]);
+      if i % 2 == 0 {
+        // plausible sizes
+        for b in input.iter_mut().filter(|b| **b > 64) {
+          *b %= 8;
+        }
+      }
+      for write_size in [false, true].iter() {
+        let mut sw = SizedWindows::new(SizedWindowsConfig { write_size : *write_size, .. conf.clone() });
+        let mut r = &input[..];
+        let e = decode(&mut r, len, &mut sw).unwrap_err();
+        if e.kind() == ErrorKind::InvalidData {
+          match window_error(Err(e)) {
+            WindowError::TooBig { max, .. } => assert!(max == 64),
+            WindowError::ZeroSize => (),
+            WindowError::InvalidParams(_) => panic!("invalid params"),
+          }
+        } else {
+          assert!(e.kind() == ErrorKind::UnexpectedEof);
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn windows_skip_test () {
+    use readwrite_comp::ExtWrite;
+    let m1 : Vec<u8> = (0..150).collect();
+    let mut w = Vec::new();
+    let mut sw = SizedWindows::new(Params3);
+    for m in [&m1[..], &[2,3]].iter() {
+      sw.write_header(&mut w).unwrap();
+      sw.write_all_into(&mut w, m).unwrap();
+      sw.write_end(&mut w).unwrap();
+    }
+    let mut r = &w[..];
+    let mut sr = SizedWindows::new(Params3);
+    sr.read_header(&mut r).unwrap();
+    assert!(sr.skip_from(&mut r, 15).unwrap() == 15);
+    // at most end of window
+    assert!(sr.skip_from(&mut r, 100).unwrap() == 5);
+    let mut buf = [0;5];
+    sr.read_exact_from(&mut r, &mut buf).unwrap();
+    assert!(buf[..] == m1[20..25]);
+    // padding of last window is skipped as content
+    assert!(sr.discard_to_end(&mut r).unwrap() >= 125);
+    sr.read_end(&mut r).unwrap();
+    sr.read_header(&mut r).unwrap();
+    sr.read_exact_from(&mut r, &mut buf[..2]).unwrap();
+    assert!(buf[..2] == [2,3]);
+    // padding skipped in read end
+    sr.read_end(&mut r).unwrap();
+    assert!(r.is_empty());
+  }
+
+  #[test]
+  fn windows_vectored_test () {
+    use readwrite_comp::ExtWrite;
+    let m1 : Vec<u8> = (0..50).collect();
+    let mut w = CallWrite(Vec::new(), Vec::new());
+    let mut sw = SizedWindows::new(Params3);
+    sw.write_header(&mut w).unwrap();
+    sw.write_all_into(&mut w, &m1[..]).unwrap();
+    sw.write_end(&mut w).unwrap();
+    // size and window content, then marker, size and window content
+    assert!(w.1[..2] == [8 + 20, 1 + 8 + 26]);
+    let mut r = &w.0[..];
+    let mut buf = vec![0;50];
+    let mut sr = SizedWindows::new(Params3);
+    sr.read_header(&mut r).unwrap();
+    sr.read_exact_from(&mut r, &mut buf[..]).unwrap();
+    assert!(buf == m1);
+    sr.read_end(&mut r).unwrap();
+    assert!(r.is_empty());
+    // empty message
+    let mut w = CallWrite(Vec::new(), Vec::new());
+    sw.write_header(&mut w).unwrap();
+    sw.write_end(&mut w).unwrap();
+    assert!(w.0.len() == 8 + 20 + 1);
+  }
+
+  #[test]
+  fn windows_message_test () {
+    let mut mw = MessageWriter::new(Cursor::new(Vec::new()), SizedWindows::new(Params4));
+    let m1 = [1;150];
+    mw.write_message(&m1[..]).unwrap();
+    mw.write_message(&[2,3]).unwrap();
+    mw.write_message(&[]).unwrap();
+    let (mut c, _) = mw.into_inner();
+    c.set_position(0);
+    let mut mr = MessageReader::new(c, SizedWindows::new(Params4));
+    // padding of last window is read as content
+    assert!(mr.read_message(2048).unwrap().unwrap().starts_with(&m1[..]));
+    assert!(mr.read_message(2048).unwrap().unwrap().starts_with(&[2,3]));
+    assert!(mr.read_message(2048).unwrap().is_some());
+    assert!(mr.read_message(2048).unwrap().is_none());
+  }
+
+  #[test]
+  fn windows_auto_read_end_test () {
+    let mut c = Cursor::new(Vec::new());
+    let mut sw = SizedWindows::new(Params4);
+    for m in [&[1;30][..], &[2,3][..]].iter() {
+      let mut compw = CompW::new(&mut c, &mut sw);
+      compw.write_all(m).unwrap();
+      compw.finish().unwrap();
+    }
+    c.set_position(0);
+    let mut sr = SizedWindows::new(Params4);
+    let mut compr = CompR::new(&mut c, &mut sr).with_auto_read_end(true);
+    let mut res = Vec::new();
+    compr.read_to_end(&mut res).unwrap();
+    // padding of last window is read as content
+    assert!(res.starts_with(&[1;30][..]));
+    res.clear();
+    compr.read_to_end(&mut res).unwrap();
+    assert!(res.starts_with(&[2,3][..]));
+    // end of stream before next window size : no more message
+    assert!(compr.read(&mut [0]).unwrap() == 0);
+    res.clear();
+    compr.read_to_end(&mut res).unwrap();
+    assert!(res.is_empty());
+  }
+
+  #[cfg(feature="async")]
+  #[test]
+  fn windows_async_multi_test () {
+    let mut et = MultiWExt::new(vec![SizedWindows::new(Params1), SizedWindows::new(Params1), SizedWindows::new(Params1)]);
+    let mut et2 = MultiRExt::new(vec![SizedWindows::new(Params1), SizedWindows::new(Params1), SizedWindows::new(Params1)]);
+    test_bytes_wr_async(
+      150,
+      7,
+      &mut et,
+      &mut et2,
+    ).unwrap();
+    let mut et = MultiWExt::new(vec![SizedWindows::new(Params4), SizedWindows::new(Params4)]);
+    let mut et2 = MultiRExt::new(vec![SizedWindows::new(Params4), SizedWindows::new(Params4)]);
+    test_bytes_w_async_r_sync(
+      150,
+      15,
+      &mut et,
+      &mut et2,
+    ).unwrap();
+    test_bytes_w_sync_r_async(
+      150,
+      15,
+      &mut et,
+      &mut et2,
+    ).unwrap();
+  }
+
+  #[test]
+  fn windows_would_block_test () {
+    use readwrite_comp::ExtWrite;
+    let messages : Vec<Vec<u8>> = vec![vec![], (0..50).collect(), vec![7]];
+    let mut ew = SizedWindows::new(Params3);
+    let mut w = FaultWrite::new(ShortWrite(Vec::new(), 3), ErrorKind::WouldBlock, 2);
+    for m in messages.iter() {
+      let blocking = &[ErrorKind::WouldBlock];
+      retry_blocking(blocking, || ew.write_header(&mut w)).unwrap();
+      let mut tot = 0;
+      while tot < m.len() {
+        tot += retry_blocking(blocking, || ew.write_into(&mut w, &m[tot..])).unwrap();
+      }
+      retry_blocking(blocking, || ew.write_end(&mut w)).unwrap();
+    }
+    let mut er = SizedWindows::new(Params3);
+    let mut r = &(w.inner).0[..];
+    for m in messages.iter() {
+      er.read_header(&mut r).unwrap();
+      let mut buf = vec![0;m.len()];
+      er.read_exact_from(&mut r, &mut buf[..]).unwrap();
+      assert!(&buf == m);
+      er.read_end(&mut r).unwrap();
+    }
+    assert!(r.is_empty());
+  }
+
+}